@@ -0,0 +1,587 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::{path::PathBuf, sync::OnceLock, time::Duration};
+
+const DEFAULT_LISTEN: &str = "localhost:6000";
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+const DEFAULT_WARN_MS: u64 = 500;
+const DEFAULT_SLOW_MS: u64 = 5000;
+const DEFAULT_SQL_INDENT: u8 = 2;
+const DEFAULT_N_PLUS_ONE_THRESHOLD: usize = 10;
+const DEFAULT_N_PLUS_ONE_WINDOW_MS: u64 = 1000;
+const DEFAULT_DASHBOARD_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Command-line arguments. Every option overrides the matching value from the config file.
+#[derive(Parser, Debug)]
+#[command(
+    name = "pgquerymon",
+    version,
+    about = "Terminal monitor for Npgsql SQL queries"
+)]
+pub struct Cli {
+    /// Path to the config file (default: $XDG_CONFIG_HOME/pgquerymon/config.toml)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// TCP address to listen on for NpgsqlLogger connections
+    #[arg(long, global = true, value_name = "ADDR")]
+    pub listen: Option<String>,
+
+    /// Also listen on a Unix domain socket at this path
+    #[arg(long, global = true, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// Listen on TCP, overriding tcp = false from the config file
+    #[arg(long, global = true, overrides_with = "no_tcp")]
+    pub tcp: bool,
+
+    /// Do not listen on TCP (requires --socket)
+    #[arg(long, global = true, overrides_with = "tcp")]
+    pub no_tcp: bool,
+
+    /// Maximum number of queries kept in memory
+    #[arg(long, global = true, value_name = "N")]
+    pub max_entries: Option<usize>,
+
+    /// Maximum total size of retained SQL text, e.g. 64MB
+    #[arg(long, global = true, value_name = "SIZE", value_parser = parse_size)]
+    pub max_sql_bytes: Option<u64>,
+
+    /// Drop queries older than this, e.g. 30m or 2h
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parse_age)]
+    pub retention: Option<Duration>,
+
+    /// Duration (ms) at which queries are coloured yellow
+    #[arg(long, global = true, value_name = "MS")]
+    pub warn_ms: Option<u64>,
+
+    /// Duration (ms) at which queries are coloured red
+    #[arg(long, global = true, value_name = "MS")]
+    pub slow_ms: Option<u64>,
+
+    /// Number of spaces used to indent formatted SQL
+    #[arg(long, global = true, value_name = "N")]
+    pub sql_indent: Option<u8>,
+
+    /// Uppercase SQL keywords when formatting
+    #[arg(long, global = true, overrides_with = "no_sql_uppercase")]
+    pub sql_uppercase: bool,
+
+    /// Keep SQL keywords as written, overriding sql_uppercase from the config file
+    #[arg(long, global = true, overrides_with = "sql_uppercase")]
+    pub no_sql_uppercase: bool,
+
+    /// Keep queries from different sources in separate groups
+    #[arg(long, global = true, overrides_with = "no_group_by_source")]
+    pub group_by_source: bool,
+
+    /// Share groups between sources, overriding group_by_source from the config file
+    #[arg(long, global = true, overrides_with = "group_by_source")]
+    pub no_group_by_source: bool,
+
+    /// How queries are grouped at startup; g cycles through the modes
+    #[arg(long, global = true, value_enum, value_name = "MODE")]
+    pub grouping: Option<GroupingMode>,
+
+    /// Number of identical queries from one caller that is flagged as N+1
+    #[arg(long, global = true, value_name = "N")]
+    pub n_plus_one_threshold: Option<usize>,
+
+    /// Time window (ms) in which repeated queries count towards N+1 detection
+    #[arg(long, global = true, value_name = "MS")]
+    pub n_plus_one_window_ms: Option<u64>,
+
+    /// Time span of the dashboard's query rate chart, e.g. 5m
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parse_age)]
+    pub dashboard_window: Option<Duration>,
+
+    /// Append every received line to this JSONL file
+    #[arg(long, global = true, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Print queries to stdout instead of starting the terminal UI
+    #[arg(long, global = true)]
+    pub no_tui: bool,
+
+    /// Output format used with --no-tui
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// When to colour --no-tui text output
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Print formatted multi-line SQL with --no-tui instead of one line per query
+    #[arg(long, global = true)]
+    pub formatted_sql: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One human readable line per query
+    Text,
+    /// One normalized JSON message per line
+    Json,
+}
+
+/// Ways to group the main list. The TUI cycles through them in this order.
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupingMode {
+    /// Endpoint and HTTP method
+    #[default]
+    Endpoint,
+    /// Request instance, keyed on request_id or trace_id
+    Request,
+    /// Namespace → class → method tree of the callers
+    Caller,
+    /// Normalized statement
+    Fingerprint,
+    /// Table the statement mainly reads or writes
+    Table,
+    /// Connection or application the queries came from
+    Source,
+    /// No groups, every query in chronological order
+    Tail,
+}
+
+impl GroupingMode {
+    const ALL: [GroupingMode; 7] = [
+        GroupingMode::Endpoint,
+        GroupingMode::Request,
+        GroupingMode::Caller,
+        GroupingMode::Fingerprint,
+        GroupingMode::Table,
+        GroupingMode::Source,
+        GroupingMode::Tail,
+    ];
+
+    /// The mode after this one, wrapping around at the end.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupingMode::Endpoint => "endpoint",
+            GroupingMode::Request => "request",
+            GroupingMode::Caller => "caller",
+            GroupingMode::Fingerprint => "fingerprint",
+            GroupingMode::Table => "table",
+            GroupingMode::Source => "source",
+            GroupingMode::Tail => "tail",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Colour when stdout is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Replay a session recorded with --record instead of listening for connections
+    Replay {
+        /// Recording to replay
+        file: PathBuf,
+
+        /// Playback speed multiplier when replaying at the original pace
+        #[arg(long, default_value_t = 1.0, value_name = "X")]
+        speed: f64,
+
+        /// Load the whole recording at once instead of at the original pace
+        #[arg(long)]
+        instant: bool,
+    },
+}
+
+// Values read from the TOML config file; anything missing falls back to defaults
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    listen: Option<String>,
+    socket: Option<PathBuf>,
+    tcp: Option<bool>,
+    max_entries: Option<usize>,
+    max_sql_bytes: Option<String>,
+    retention: Option<String>,
+    warn_ms: Option<u64>,
+    slow_ms: Option<u64>,
+    sql_indent: Option<u8>,
+    sql_uppercase: Option<bool>,
+    group_by_source: Option<bool>,
+    grouping: Option<GroupingMode>,
+    n_plus_one_threshold: Option<usize>,
+    n_plus_one_window_ms: Option<u64>,
+    dashboard_window: Option<String>,
+}
+
+/// Duration thresholds used to colour queries from green over yellow to red.
+#[derive(Debug, Clone, Copy)]
+pub struct DurationThresholds {
+    pub warn_ms: u64,
+    pub slow_ms: u64,
+}
+
+/// Effective settings after merging defaults, the config file and CLI flags.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub listen: String,
+    pub tcp_enabled: bool,
+    pub socket: Option<PathBuf>,
+    pub max_entries: usize,
+    pub max_sql_bytes: Option<u64>,
+    pub max_age: Option<Duration>,
+    pub thresholds: DurationThresholds,
+    pub sql_indent: u8,
+    pub sql_uppercase: bool,
+    pub group_by_source: bool,
+    pub grouping: GroupingMode,
+    pub n_plus_one_threshold: usize,
+    pub n_plus_one_window_ms: u64,
+    pub dashboard_window: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen: DEFAULT_LISTEN.to_string(),
+            tcp_enabled: true,
+            socket: None,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_sql_bytes: None,
+            max_age: None,
+            thresholds: DurationThresholds {
+                warn_ms: DEFAULT_WARN_MS,
+                slow_ms: DEFAULT_SLOW_MS,
+            },
+            sql_indent: DEFAULT_SQL_INDENT,
+            sql_uppercase: false,
+            group_by_source: false,
+            grouping: GroupingMode::default(),
+            n_plus_one_threshold: DEFAULT_N_PLUS_ONE_THRESHOLD,
+            n_plus_one_window_ms: DEFAULT_N_PLUS_ONE_WINDOW_MS,
+            dashboard_window: DEFAULT_DASHBOARD_WINDOW,
+        }
+    }
+}
+
+impl Config {
+    /// Build the effective config from the CLI arguments and the (optional) config file.
+    pub fn load(cli: &Cli) -> anyhow::Result<Self> {
+        let file = match cli.config.clone().or_else(default_config_path) {
+            Some(path) if path.exists() => {
+                let contents = std::fs::read_to_string(&path).map_err(|e| {
+                    anyhow::anyhow!("failed to read config file {}: {}", path.display(), e)
+                })?;
+                toml::from_str::<FileConfig>(&contents)
+                    .map_err(|e| anyhow::anyhow!("invalid config file {}: {}", path.display(), e))?
+            }
+            Some(path) if cli.config.is_some() => {
+                anyhow::bail!("config file {} does not exist", path.display())
+            }
+            _ => FileConfig::default(),
+        };
+
+        let defaults = Config::default();
+        let config = Config {
+            listen: cli
+                .listen
+                .clone()
+                .or(file.listen)
+                .unwrap_or(defaults.listen),
+            tcp_enabled: flag(cli.tcp, cli.no_tcp)
+                .or(file.tcp)
+                .unwrap_or(defaults.tcp_enabled),
+            socket: cli.socket.clone().or(file.socket),
+            max_entries: cli
+                .max_entries
+                .or(file.max_entries)
+                .unwrap_or(defaults.max_entries),
+            max_sql_bytes: match cli.max_sql_bytes {
+                Some(bytes) => Some(bytes),
+                None => file
+                    .max_sql_bytes
+                    .as_deref()
+                    .map(parse_size)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("invalid max_sql_bytes: {}", e))?,
+            },
+            max_age: match cli.retention {
+                Some(age) => Some(age),
+                None => file
+                    .retention
+                    .as_deref()
+                    .map(parse_age)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("invalid retention: {}", e))?,
+            },
+            thresholds: DurationThresholds {
+                warn_ms: cli
+                    .warn_ms
+                    .or(file.warn_ms)
+                    .unwrap_or(defaults.thresholds.warn_ms),
+                slow_ms: cli
+                    .slow_ms
+                    .or(file.slow_ms)
+                    .unwrap_or(defaults.thresholds.slow_ms),
+            },
+            sql_indent: cli
+                .sql_indent
+                .or(file.sql_indent)
+                .unwrap_or(defaults.sql_indent),
+            sql_uppercase: flag(cli.sql_uppercase, cli.no_sql_uppercase)
+                .or(file.sql_uppercase)
+                .unwrap_or(defaults.sql_uppercase),
+            group_by_source: flag(cli.group_by_source, cli.no_group_by_source)
+                .or(file.group_by_source)
+                .unwrap_or(defaults.group_by_source),
+            grouping: cli.grouping.or(file.grouping).unwrap_or(defaults.grouping),
+            n_plus_one_threshold: cli
+                .n_plus_one_threshold
+                .or(file.n_plus_one_threshold)
+                .unwrap_or(defaults.n_plus_one_threshold),
+            n_plus_one_window_ms: cli
+                .n_plus_one_window_ms
+                .or(file.n_plus_one_window_ms)
+                .unwrap_or(defaults.n_plus_one_window_ms),
+            dashboard_window: match cli.dashboard_window {
+                Some(window) => window,
+                None => file
+                    .dashboard_window
+                    .as_deref()
+                    .map(parse_age)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("invalid dashboard_window: {}", e))?
+                    .unwrap_or(defaults.dashboard_window),
+            },
+        };
+
+        if !config.tcp_enabled && config.socket.is_none() {
+            anyhow::bail!("TCP is disabled and no socket path is set, nothing to listen on");
+        }
+        if cfg!(not(unix)) && config.socket.is_some() {
+            anyhow::bail!("Unix domain sockets are not supported on this platform");
+        }
+        if config.n_plus_one_threshold < 2 {
+            anyhow::bail!("n_plus_one_threshold must be at least 2");
+        }
+        if config.max_entries == 0 {
+            anyhow::bail!("max_entries must be greater than zero");
+        }
+        if config.thresholds.warn_ms >= config.thresholds.slow_ms {
+            anyhow::bail!(
+                "warn_ms ({}) must be lower than slow_ms ({})",
+                config.thresholds.warn_ms,
+                config.thresholds.slow_ms
+            );
+        }
+        Ok(config)
+    }
+}
+
+// A --flag / --no-flag pair: whichever was given last wins, None when neither was given
+fn flag(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+// Sizes like "64MB", "512KB" or a plain number of bytes
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a size like 64MB", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("unknown size unit in '{}'", value)),
+    };
+    match number.checked_mul(multiplier) {
+        Some(0) | None => Err(format!("'{}' is not a usable size", value)),
+        Some(bytes) => Ok(bytes),
+    }
+}
+
+// Durations like "90s", "30m" or "2h"
+fn parse_age(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a duration like 30m", value))?;
+    let multiplier: u64 = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => return Err(format!("'{}' needs a unit: s, m, h or d", value)),
+    };
+    match number.checked_mul(multiplier) {
+        Some(0) => Err("duration must be longer than zero".to_string()),
+        None => Err(format!("'{}' is too long", value)),
+        Some(seconds) => Ok(Duration::from_secs(seconds)),
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("pgquerymon").join("config.toml"))
+}
+
+static SETTINGS: OnceLock<Config> = OnceLock::new();
+
+/// Install the effective config for the rest of the process. Only the first call has an effect.
+pub fn init(config: Config) {
+    let _ = SETTINGS.set(config);
+}
+
+/// Access the effective config, falling back to defaults when `init` has not been called.
+pub fn get() -> &'static Config {
+    SETTINGS.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Load the config for `args` with `toml` as the config file
+    fn load(toml: &str, args: &[&str]) -> anyhow::Result<Config> {
+        let path = std::env::temp_dir().join(format!(
+            "pgquerymon-test-{}-{:?}.toml",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        std::fs::write(&path, toml).unwrap();
+        let mut argv = vec!["pgquerymon", "--config", path.to_str().unwrap()];
+        argv.extend_from_slice(args);
+        let config = Config::load(&Cli::parse_from(argv));
+        std::fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn sizes_with_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("2KB"), Ok(2048));
+        assert_eq!(parse_size(" 64mb "), Ok(64 * 1024 * 1024));
+        assert_eq!(parse_size("1 G"), Ok(1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("10TB").is_err());
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("18446744073709551615GB").is_err());
+    }
+
+    #[test]
+    fn ages_with_units() {
+        assert_eq!(parse_age("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_age("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_age("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(parse_age("1d"), Ok(Duration::from_secs(24 * 60 * 60)));
+    }
+
+    #[test]
+    fn invalid_ages() {
+        assert_eq!(
+            parse_age("30"),
+            Err("'30' needs a unit: s, m, h or d".to_string())
+        );
+        assert_eq!(
+            parse_age("0m"),
+            Err("duration must be longer than zero".to_string())
+        );
+        assert_eq!(
+            parse_age("18446744073709551615d"),
+            Err("'18446744073709551615d' is too long".to_string())
+        );
+        assert!(parse_age("m").is_err());
+    }
+
+    #[test]
+    fn file_values_apply_when_no_flag_is_given() {
+        let config = load(
+            "max_entries = 50\nretention = \"10m\"\nsql_uppercase = true\ngrouping = \"caller\"",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(config.max_entries, 50);
+        assert_eq!(config.max_age, Some(Duration::from_secs(600)));
+        assert!(config.sql_uppercase);
+        assert_eq!(config.grouping, GroupingMode::Caller);
+        assert_eq!(config.listen, DEFAULT_LISTEN);
+    }
+
+    #[test]
+    fn flags_override_the_file() {
+        let config = load(
+            "max_entries = 50\nretention = \"10m\"\ngrouping = \"caller\"",
+            &[
+                "--max-entries",
+                "7",
+                "--retention",
+                "1h",
+                "--grouping",
+                "tail",
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.max_entries, 7);
+        assert_eq!(config.max_age, Some(Duration::from_secs(3600)));
+        assert_eq!(config.grouping, GroupingMode::Tail);
+    }
+
+    #[test]
+    fn negative_flags_override_the_file() {
+        let toml = "sql_uppercase = true\ngroup_by_source = true";
+        let config = load(toml, &["--no-sql-uppercase", "--no-group-by-source"]).unwrap();
+        assert!(!config.sql_uppercase);
+        assert!(!config.group_by_source);
+        // The last of a --flag / --no-flag pair wins
+        let config = load(toml, &["--no-sql-uppercase", "--sql-uppercase"]).unwrap();
+        assert!(config.sql_uppercase);
+    }
+
+    #[test]
+    fn tcp_can_be_turned_back_on() {
+        let toml = "tcp = false\nsocket = \"/tmp/pgquerymon-test.sock\"";
+        assert!(!load(toml, &[]).unwrap().tcp_enabled);
+        assert!(load(toml, &["--tcp"]).unwrap().tcp_enabled);
+        assert!(
+            !load("", &["--no-tcp", "--socket", "/tmp/s.sock"])
+                .unwrap()
+                .tcp_enabled
+        );
+    }
+
+    #[test]
+    fn invalid_file_values_are_errors() {
+        assert!(load("retention = \"soon\"", &[]).is_err());
+        assert!(load("max_entries = 0", &[]).is_err());
+        assert!(load("warn_ms = 900\nslow_ms = 100", &[]).is_err());
+        assert!(load("tcp = false", &[]).is_err());
+    }
+}
@@ -0,0 +1,545 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::SqlLogMessage;
+
+/// A parsed filter expression. Terms are ANDed when written next to each other:
+///
+/// - `orders` matches method, endpoint, caller class/method and source (case-insensitive)
+/// - `sql:orders`, `method:POST`, `endpoint:/api`, `class:OrderRepo`, `caller:Load`, `source:api`,
+///   `request:0HN4` (request_id or trace_id)
+/// - `sql~/update\s+orders/` matches a field against a case-insensitive regex
+/// - `dur>200`, `dur<=1s` compare the duration in milliseconds (or with an `s` suffix)
+/// - `rows>1000`, `affected>=100` compare rows returned and affected
+/// - `-term` / `NOT term` negate, `OR` and parentheses combine
+#[derive(Debug, Clone)]
+pub enum Filter {
+    All,
+    Text(String),
+    Field(Field, Matcher),
+    Metric(Metric, Comparison, u64),
+    Not(Box<Filter>),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Field {
+    Sql,
+    Method,
+    Endpoint,
+    Class,
+    Caller,
+    Source,
+    Request,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Metric {
+    Duration,
+    RowsReturned,
+    RowsAffected,
+}
+
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Contains(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Filter {
+    pub fn matches(&self, msg: &SqlLogMessage) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Text(needle) => [
+                Field::Method,
+                Field::Endpoint,
+                Field::Class,
+                Field::Caller,
+                Field::Source,
+            ]
+            .into_iter()
+            .any(|field| {
+                field_value(msg, field).is_some_and(|v| v.to_lowercase().contains(needle))
+            }),
+            Filter::Field(field, matcher) => {
+                field_value(msg, *field).is_some_and(|value| match matcher {
+                    Matcher::Contains(needle) => value.to_lowercase().contains(needle),
+                    Matcher::Regex(regex) => regex.is_match(value),
+                })
+            }
+            // Messages without the value never match, whichever way the comparison goes
+            Filter::Metric(metric, comparison, limit) => {
+                metric_value(msg, *metric).is_some_and(|value| match comparison {
+                    Comparison::Less => value < *limit,
+                    Comparison::LessOrEqual => value <= *limit,
+                    Comparison::Equal => value == *limit,
+                    Comparison::GreaterOrEqual => value >= *limit,
+                    Comparison::Greater => value > *limit,
+                })
+            }
+            Filter::Not(inner) => !inner.matches(msg),
+            Filter::And(filters) => filters.iter().all(|filter| filter.matches(msg)),
+            Filter::Or(filters) => filters.iter().any(|filter| filter.matches(msg)),
+        }
+    }
+}
+
+fn field_value(msg: &SqlLogMessage, field: Field) -> Option<&str> {
+    match field {
+        Field::Sql => Some(msg.statement.as_str()),
+        // Queries outside a request are shown as CALL, so they can be filtered as such
+        Field::Method => Some(msg.http_method.as_deref().unwrap_or("CALL")),
+        Field::Endpoint => msg.endpoint.as_deref(),
+        Field::Class => msg.caller_class.as_deref(),
+        Field::Caller => msg.caller_method.as_deref(),
+        Field::Source => msg.source.as_deref(),
+        Field::Request => msg.request_id.as_deref().or(msg.trace_id.as_deref()),
+    }
+}
+
+fn metric_value(msg: &SqlLogMessage, metric: Metric) -> Option<u64> {
+    match metric {
+        Metric::Duration => Some(msg.duration),
+        Metric::RowsReturned => msg.rows_returned,
+        Metric::RowsAffected => msg.rows_affected,
+    }
+}
+
+/// Parse the text of the filter box. An empty filter matches everything.
+pub fn parse_filter(text: &str) -> Result<Filter, String> {
+    let tokens = lex(text)?;
+    if tokens.is_empty() {
+        return Ok(Filter::All);
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let filter = parser.parse_or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(filter),
+        Some(Token::Close) => Err("unmatched ')'".to_string()),
+        Some(token) => Err(format!("unexpected {}", token.describe())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+            Token::And => "AND".to_string(),
+            Token::Or => "OR".to_string(),
+            Token::Not => "NOT".to_string(),
+            Token::Term(term) => format!("'{}'", term),
+        }
+    }
+}
+
+// Split into parentheses, keywords and terms. Quoted values and /regex/ bodies may contain spaces
+// and parentheses.
+fn lex(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '(' {
+            tokens.push(Token::Open);
+            i += 1;
+            continue;
+        }
+        if c == ')' {
+            tokens.push(Token::Close);
+            i += 1;
+            continue;
+        }
+
+        let mut term = String::new();
+        while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')' {
+            let c = chars[i];
+            if c == '"' {
+                // Quoted value, kept without the quotes
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    term.push(chars[i]);
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err("unterminated quote".to_string());
+                }
+                i += 1;
+            } else if c == '/' && term.ends_with('~') {
+                // Regex body up to the next unescaped slash
+                term.push(c);
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err("unterminated regex, expected closing '/'".to_string()),
+                        Some('\\') if chars.get(i + 1) == Some(&'/') => {
+                            term.push('/');
+                            i += 2;
+                        }
+                        Some('/') => {
+                            term.push('/');
+                            i += 1;
+                            break;
+                        }
+                        Some(&other) => {
+                            term.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+            } else {
+                term.push(c);
+                i += 1;
+            }
+        }
+
+        let token = match term.as_str() {
+            "AND" | "and" | "&&" => Token::And,
+            "OR" | "or" | "||" => Token::Or,
+            "NOT" | "not" | "-" => Token::Not,
+            _ => match term.strip_prefix('-') {
+                Some(rest) => {
+                    tokens.push(Token::Not);
+                    Token::Term(rest.to_string())
+                }
+                None => Token::Term(term),
+            },
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+// Recursive descent: or := and (OR and)*, and := unary (AND? unary)*, unary := NOT unary | atom
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, String> {
+        let mut filters = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            filters.push(self.parse_and()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.remove(0)
+        } else {
+            Filter::Or(filters)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, String> {
+        let mut filters = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.pos += 1;
+                    filters.push(self.parse_unary()?);
+                }
+                Some(Token::Open | Token::Not | Token::Term(_)) => {
+                    filters.push(self.parse_unary()?);
+                }
+                _ => break,
+            }
+        }
+        Ok(if filters.len() == 1 {
+            filters.remove(0)
+        } else {
+            Filter::And(filters)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, String> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Filter::Not(Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Open) => {
+                self.pos += 1;
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("missing ')'".to_string());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(Token::Term(term)) => {
+                self.pos += 1;
+                parse_term(&term)
+            }
+            Some(token) => Err(format!("unexpected {}", token.describe())),
+            None => Err("unexpected end of filter".to_string()),
+        }
+    }
+}
+
+fn parse_term(term: &str) -> Result<Filter, String> {
+    let Some(split) = term.find([':', '~', '<', '>', '=']) else {
+        return Ok(Filter::Text(term.to_lowercase()));
+    };
+    let (name, rest) = term.split_at(split);
+    let name = name.to_lowercase();
+
+    let metric = match name.as_str() {
+        "dur" | "duration" => Some(Metric::Duration),
+        "rows" => Some(Metric::RowsReturned),
+        "affected" => Some(Metric::RowsAffected),
+        _ => None,
+    };
+    if let Some(metric) = metric {
+        return parse_metric_predicate(&name, metric, rest);
+    }
+
+    let field = match name.as_str() {
+        "sql" => Field::Sql,
+        "method" => Field::Method,
+        "endpoint" => Field::Endpoint,
+        "class" => Field::Class,
+        "caller" => Field::Caller,
+        "source" => Field::Source,
+        "request" => Field::Request,
+        // Anything that does not start with a field name (e.g. "/api/a:b", "http://x" or
+        // "OrderRepo:Load") is plain text
+        _ => return Ok(Filter::Text(term.to_lowercase())),
+    };
+
+    if let Some(value) = rest.strip_prefix(':') {
+        if value.is_empty() {
+            return Err(format!("missing value after '{}:'", name));
+        }
+        return Ok(Filter::Field(
+            field,
+            Matcher::Contains(value.to_lowercase()),
+        ));
+    }
+    if let Some(pattern) = rest.strip_prefix('~') {
+        let pattern = pattern
+            .strip_prefix('/')
+            .and_then(|p| p.strip_suffix('/'))
+            .ok_or_else(|| format!("expected {}~/regex/", name))?;
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|_| format!("invalid regex /{}/", pattern))?;
+        return Ok(Filter::Field(field, Matcher::Regex(regex)));
+    }
+    Err(format!("expected '{}:' or '{}~/regex/'", name, name))
+}
+
+fn parse_metric_predicate(name: &str, metric: Metric, rest: &str) -> Result<Filter, String> {
+    let (comparison, value) = if let Some(value) = rest.strip_prefix(">=") {
+        (Comparison::GreaterOrEqual, value)
+    } else if let Some(value) = rest.strip_prefix("<=") {
+        (Comparison::LessOrEqual, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Comparison::Greater, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Comparison::Less, value)
+    } else if let Some(value) = rest.strip_prefix('=').or_else(|| rest.strip_prefix(':')) {
+        (Comparison::Equal, value)
+    } else {
+        return Err(format!("expected {0}>N, {0}<N or {0}=N", name));
+    };
+
+    let limit = match metric {
+        Metric::Duration => {
+            if let Some(ms) = value.strip_suffix("ms") {
+                ms.parse::<u64>().ok()
+            } else if let Some(seconds) = value.strip_suffix('s') {
+                seconds.parse::<f64>().ok().map(|s| (s * 1000.0) as u64)
+            } else {
+                value.parse::<u64>().ok()
+            }
+        }
+        Metric::RowsReturned | Metric::RowsAffected => value.parse::<u64>().ok(),
+    };
+    let kind = match metric {
+        Metric::Duration => "duration",
+        Metric::RowsReturned | Metric::RowsAffected => "row count",
+    };
+    limit
+        .map(|limit| Filter::Metric(metric, comparison, limit))
+        .ok_or_else(|| format!("invalid {} '{}'", kind, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(json: &str) -> SqlLogMessage {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn orders_update() -> SqlLogMessage {
+        message(
+            r#"{"statement": "UPDATE orders SET status = $1", "duration": 250,
+                "timestamp": "2026-01-01T00:00:00Z", "endpoint": "/api/orders",
+                "http_method": "POST", "caller_class": "OrderRepo", "caller_method": "Save",
+                "rows_affected": 3}"#,
+        )
+    }
+
+    fn users_select() -> SqlLogMessage {
+        message(
+            r#"{"statement": "select * from users", "duration": 4,
+                "timestamp": "2026-01-01T00:00:00Z", "endpoint": "http://host/users",
+                "http_method": "GET", "caller_class": "UserRepo", "caller_method": "Load",
+                "rows_returned": 20}"#,
+        )
+    }
+
+    fn matches(filter: &str, msg: &SqlLogMessage) -> bool {
+        parse_filter(filter).unwrap().matches(msg)
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        assert!(matches("   ", &orders_update()));
+    }
+
+    #[test]
+    fn bare_words_match_request_and_caller_fields() {
+        assert!(matches("orderrepo", &orders_update()));
+        assert!(matches("POST", &orders_update()));
+        assert!(!matches("orderrepo", &users_select()));
+        // The statement is only searched with sql:
+        assert!(!matches("status", &orders_update()));
+    }
+
+    #[test]
+    fn field_predicates() {
+        assert!(matches("sql:status", &orders_update()));
+        assert!(matches("class:OrderRepo caller:save", &orders_update()));
+        assert!(matches(
+            "method:CALL",
+            &message(
+                r#"{"statement": "select 1", "duration": 1, "timestamp": "2026-01-01T00:00:00Z"}"#
+            )
+        ));
+        assert!(!matches("endpoint:/api", &users_select()));
+    }
+
+    #[test]
+    fn unknown_field_names_are_plain_text() {
+        assert!(matches("http://host", &users_select()));
+        assert!(matches("/api/orders", &orders_update()));
+        assert!(
+            matches!(parse_filter("OrderRepo:Save"), Ok(Filter::Text(text)) if text == "orderrepo:save")
+        );
+    }
+
+    #[test]
+    fn quoted_values_keep_spaces() {
+        assert!(matches(r#"sql:"set status""#, &orders_update()));
+        assert!(!matches(r#"sql:"set  status""#, &orders_update()));
+    }
+
+    #[test]
+    fn regex_predicates_are_case_insensitive() {
+        assert!(matches(r"sql~/update\s+orders/", &orders_update()));
+        assert!(matches(
+            r"endpoint~/^\/api\/(orders|items)$/",
+            &orders_update()
+        ));
+        assert!(!matches(r"sql~/^select/", &orders_update()));
+    }
+
+    #[test]
+    fn metric_comparisons() {
+        assert!(matches("dur>200", &orders_update()));
+        assert!(matches("dur<=0.25s", &orders_update()));
+        assert!(matches("duration=250ms", &orders_update()));
+        assert!(!matches("dur<250", &orders_update()));
+        assert!(matches("affected>=3", &orders_update()));
+        assert!(matches("rows>10", &users_select()));
+        // Without a row count the comparison fails in both directions
+        assert!(!matches("rows>0", &orders_update()));
+        assert!(!matches("rows<=0", &orders_update()));
+    }
+
+    #[test]
+    fn negation() {
+        assert!(matches("-method:GET", &orders_update()));
+        assert!(matches("NOT method:GET", &orders_update()));
+        assert!(!matches("not -method:GET", &orders_update()));
+        assert!(matches("- users", &orders_update()));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // method:GET OR (sql:status AND dur<10)
+        assert!(!matches(
+            "method:GET OR sql:status dur<10",
+            &orders_update()
+        ));
+        assert!(matches("method:GET OR sql:status dur<10", &users_select()));
+        assert!(matches(
+            "(method:GET OR sql:status) dur>10",
+            &orders_update()
+        ));
+        assert!(matches(
+            "method:GET || sql:status && dur>10",
+            &orders_update()
+        ));
+    }
+
+    #[test]
+    fn negation_binds_tighter_than_and() {
+        assert!(matches("-method:GET dur>200", &orders_update()));
+        assert!(!matches("-(method:POST dur>200)", &orders_update()));
+        assert!(matches("-(method:POST dur>900)", &orders_update()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |text: &str| parse_filter(text).unwrap_err();
+        assert_eq!(error(r#"sql:"order by"#), "unterminated quote");
+        assert_eq!(
+            error("sql~/order"),
+            "unterminated regex, expected closing '/'"
+        );
+        assert_eq!(error("sql~/(/"), "invalid regex /(/");
+        assert_eq!(error("sql~order"), "expected sql~/regex/");
+        assert_eq!(error("sql:"), "missing value after 'sql:'");
+        assert_eq!(error("sql>3"), "expected 'sql:' or 'sql~/regex/'");
+        assert_eq!(error("dur>fast"), "invalid duration 'fast'");
+        assert_eq!(error("rows~3"), "expected rows>N, rows<N or rows=N");
+        assert_eq!(error("(dur>1"), "missing ')'");
+        assert_eq!(error("dur>1)"), "unmatched ')'");
+        assert_eq!(error("dur>1 OR"), "unexpected end of filter");
+        assert_eq!(error("AND dur>1"), "unexpected AND");
+    }
+}
@@ -0,0 +1,492 @@
+/// Normalize a statement into a fingerprint, similar to pg_stat_statements: comments are dropped,
+/// whitespace is collapsed, keywords and identifiers are lowercased, string and numeric literals
+/// become `$n` placeholders and IN-lists, ARRAY literals and VALUES rows collapse to `(...)` /
+/// `[...]`.
+pub fn fingerprint_sql(sql: &str) -> String {
+    // Batch markers are display hints from the logger, not part of the statement shape
+    let sql: String = sql
+        .lines()
+        .filter(|line| !line.starts_with("[-- Batch Command"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let tokens = tokenize(&sql);
+    let mut next_placeholder = highest_positional_parameter(&tokens) + 1;
+
+    let normalized: Vec<String> = tokens
+        .into_iter()
+        .map(|token| match token {
+            Token::Literal => LITERAL.to_string(),
+            Token::Word(word) => word.to_lowercase(),
+            Token::QuotedIdentifier(ident) => ident,
+            Token::Parameter(param) => param,
+            Token::Symbol(symbol) => symbol.to_string(),
+        })
+        .collect();
+
+    // Literals are numbered after the lists are collapsed, so the length of a list does not shift
+    // the numbers of the placeholders after it
+    let collapsed: Vec<String> = collapse_lists(normalized)
+        .into_iter()
+        .map(|token| {
+            if token != LITERAL {
+                return token;
+            }
+            let placeholder = format!("${}", next_placeholder);
+            next_placeholder += 1;
+            placeholder
+        })
+        .collect();
+    join_tokens(&collapsed)
+}
+
+// Stands in for a literal until the placeholders are numbered. No SQL token looks like this.
+const LITERAL: &str = "$?";
+
+/// The table a statement is mainly about: the target of INSERT INTO, UPDATE and DELETE FROM, or
+/// the first table a query reads FROM or JOINs. Schema-qualified names keep their schema.
+pub fn primary_table(sql: &str) -> Option<String> {
+    let tokens = tokenize(sql);
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        let Token::Word(word) = token else {
+            return None;
+        };
+        match word.to_lowercase().as_str() {
+            // A name followed by ( is a function call here, e.g. FROM generate_series(...)
+            "from" | "join" => table_name(&tokens[i + 1..], false),
+            // ...but a column list after INSERT INTO
+            "into" | "update" => table_name(&tokens[i + 1..], true),
+            _ => None,
+        }
+    })
+}
+
+// A possibly qualified name at the start of `tokens`, after an optional ONLY. Subqueries are not
+// tables, so the search moves on past them.
+fn table_name(tokens: &[Token], allow_paren: bool) -> Option<String> {
+    let mut tokens = tokens.iter().peekable();
+    if matches!(tokens.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case("only")) {
+        tokens.next();
+    }
+    let mut name = String::new();
+    loop {
+        match tokens.next()? {
+            Token::Word(word) => name.push_str(&word.to_lowercase()),
+            Token::QuotedIdentifier(ident) => name.push_str(ident),
+            _ => return None,
+        }
+        match tokens.peek() {
+            Some(Token::Symbol('.')) => {
+                tokens.next();
+                name.push('.');
+            }
+            Some(Token::Symbol('(')) if !allow_paren => return None,
+            _ => return Some(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    QuotedIdentifier(String),
+    Parameter(String),
+    Literal,
+    Symbol(char),
+}
+
+fn tokenize(sql: &str) -> Vec<Token> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            // Line comment
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            // Block comment
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '\'' {
+            // String literal
+            i = skip_string(&chars, i + 1, false);
+            tokens.push(Token::Literal);
+        } else if (c == 'E' || c == 'e') && chars.get(i + 1) == Some(&'\'') {
+            // Escape string, E'it\'s', where a backslash escapes the next character
+            i = skip_string(&chars, i + 2, true);
+            tokens.push(Token::Literal);
+        } else if c == '"' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            i += 1;
+            let end = i.min(chars.len());
+            tokens.push(Token::QuotedIdentifier(chars[start..end].iter().collect()));
+        } else if c == ':' && chars.get(i + 1) == Some(&':') {
+            // Type cast
+            tokens.push(Token::Symbol(':'));
+            tokens.push(Token::Symbol(':'));
+            i += 2;
+        } else if let Some(end) = (c == '$').then(|| dollar_quote_end(&chars, i)).flatten() {
+            // Dollar-quoted string, $$...$$ or $tag$...$tag$
+            i = end;
+            tokens.push(Token::Literal);
+        } else if (c == '$' || c == '@' || c == ':')
+            && chars
+                .get(i + 1)
+                .is_some_and(|next| next.is_alphanumeric() || *next == '_')
+        {
+            // Positional ($1) or named (@p0, :name) parameter
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Parameter(chars[start..i].iter().collect()));
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|next| next.is_ascii_digit()))
+        {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Literal);
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+// The index just past the string literal whose body starts at `i`. '' is an escaped quote, and in
+// escape strings so is \'.
+fn skip_string(chars: &[char], mut i: usize, backslash_escapes: bool) -> usize {
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if backslash_escapes => i += 2,
+            '\'' if chars.get(i + 1) == Some(&'\'') => i += 2,
+            '\'' => break,
+            _ => i += 1,
+        }
+    }
+    i + 1
+}
+
+// The index just past a $tag$...$tag$ string starting at `start`, None when the $ does not open
+// one. Tags follow identifier rules, so $1 stays a parameter. An unterminated body runs to the end.
+fn dollar_quote_end(chars: &[char], start: usize) -> Option<usize> {
+    let tag_end =
+        (start + 1..chars.len()).find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_'))?;
+    if chars[tag_end] != '$' || chars.get(start + 1).is_some_and(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let tag = &chars[start..=tag_end];
+    Some(
+        (tag_end + 1..chars.len())
+            .find(|&j| chars[j..].starts_with(tag))
+            .map_or(chars.len(), |j| j + tag.len()),
+    )
+}
+
+fn highest_positional_parameter(tokens: &[Token]) -> usize {
+    tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Parameter(param) => param.strip_prefix('$')?.parse::<usize>().ok(),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn is_placeholder(token: &str) -> bool {
+    token.starts_with('$') || token.starts_with('@') || token.starts_with(':')
+}
+
+// Collapse "in ($1, $2, ...)", "array[$1, $2, ...]" and "values ($1, $2), ($3, $4), ..." so list
+// length and row count do not change the shape
+fn collapse_lists(tokens: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let collapsed = match (result.last().map(String::as_str), tokens[i].as_str()) {
+            (Some("in"), "(") => placeholder_list_end(&tokens, i, ")").map(|end| ("(", ")", end)),
+            (Some("array"), "[") => {
+                placeholder_list_end(&tokens, i, "]").map(|end| ("[", "]", end))
+            }
+            (Some("values"), "(") => values_rows_end(&tokens, i).map(|end| ("(", ")", end)),
+            _ => None,
+        };
+        if let Some((open, close, end)) = collapsed {
+            result.push(open.to_string());
+            result.push("...".to_string());
+            result.push(close.to_string());
+            i = end + 1;
+            continue;
+        }
+        result.push(tokens[i].clone());
+        i += 1;
+    }
+    result
+}
+
+// The index of `close` when the list opened at `open` consists of placeholders separated by
+// commas, None for anything else
+fn placeholder_list_end(tokens: &[String], open: usize, close: &str) -> Option<usize> {
+    let mut expect_value = true;
+    for (j, token) in tokens.iter().enumerate().skip(open + 1) {
+        let token = token.as_str();
+        if token == close && !expect_value {
+            return Some(j);
+        }
+        if expect_value && is_placeholder(token) {
+            expect_value = false;
+        } else if !expect_value && token == "," {
+            expect_value = true;
+        } else {
+            return None;
+        }
+    }
+    None
+}
+
+// The index of the last ")" of the VALUES rows starting at `open`, as long as every row is a
+// placeholder list
+fn values_rows_end(tokens: &[String], open: usize) -> Option<usize> {
+    let mut end = placeholder_list_end(tokens, open, ")")?;
+    while tokens.get(end + 1).map(String::as_str) == Some(",")
+        && tokens.get(end + 2).map(String::as_str) == Some("(")
+    {
+        end = placeholder_list_end(tokens, end + 2, ")")?;
+    }
+    Some(end)
+}
+
+// Join tokens with single spaces, without spaces around punctuation that reads better tight
+fn join_tokens(tokens: &[String]) -> String {
+    let mut output = String::new();
+    let mut previous: Option<&str> = None;
+    for token in tokens {
+        let tight = match (previous, token.as_str()) {
+            (None, _) => true,
+            (_, "," | ")" | "]" | "." | ";") => true,
+            (Some("(" | "[" | "."), _) => true,
+            (Some("..."), _) => false,
+            (Some(prev), "(") => {
+                // Function calls stay attached, keywords keep their space
+                !is_keyword_before_paren(prev)
+                    && prev.chars().all(|c| c.is_alphanumeric() || c == '_')
+            }
+            (Some(":"), _) | (_, ":") => true,
+            _ => false,
+        };
+        if !tight {
+            output.push(' ');
+        }
+        output.push_str(token);
+        previous = Some(token);
+    }
+    output
+}
+
+fn is_keyword_before_paren(word: &str) -> bool {
+    matches!(
+        word,
+        "in" | "values"
+            | "as"
+            | "on"
+            | "and"
+            | "or"
+            | "not"
+            | "from"
+            | "join"
+            | "where"
+            | "select"
+            | "exists"
+            | "by"
+            | "when"
+            | "then"
+            | "else"
+            | "set"
+            | "into"
+            | "using"
+            | "with"
+            | "returning"
+            | "union"
+            | "all"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals_become_numbered_placeholders() {
+        assert_eq!(
+            fingerprint_sql("SELECT * FROM users WHERE id = 42 AND name = 'bob'"),
+            "select * from users where id = $1 and name = $2"
+        );
+    }
+
+    #[test]
+    fn list_length_does_not_shift_later_placeholders() {
+        let short = fingerprint_sql("select * from t where id in (1, 2) and x = 3");
+        let long = fingerprint_sql("select * from t where id in (1, 2, 3) and x = 4");
+        assert_eq!(short, "select * from t where id in (...) and x = $1");
+        assert_eq!(short, long);
+        assert_eq!(
+            fingerprint_sql("insert into t (a) values (1), (2), (3) returning 'x'"),
+            fingerprint_sql("insert into t (a) values (1) returning 'y'")
+        );
+    }
+
+    #[test]
+    fn placeholders_continue_after_existing_parameters() {
+        assert_eq!(
+            fingerprint_sql("select * from t where a = $2 and b = 'x'"),
+            "select * from t where a = $2 and b = $3"
+        );
+    }
+
+    #[test]
+    fn doubled_quotes_stay_inside_the_literal() {
+        assert_eq!(
+            fingerprint_sql("select 'it''s -- not a comment' from t"),
+            "select $1 from t"
+        );
+    }
+
+    #[test]
+    fn escape_strings_are_one_literal() {
+        assert_eq!(
+            fingerprint_sql(r"select E'it\'s' , e'a\\' from t where x = 1"),
+            "select $1, $2 from t where x = $3"
+        );
+    }
+
+    #[test]
+    fn words_ending_in_e_are_not_escape_strings() {
+        assert_eq!(
+            fingerprint_sql("select * from t where type='x' and name=E'y'"),
+            "select * from t where type = $1 and name = $2"
+        );
+    }
+
+    #[test]
+    fn dollar_quoted_strings_are_one_literal() {
+        assert_eq!(
+            fingerprint_sql("select $$it's$$ from t where a = 1"),
+            "select $1 from t where a = $2"
+        );
+        assert_eq!(
+            fingerprint_sql("select $fn$ $1 'x' $$ $fn$, $1"),
+            "select $2, $1"
+        );
+    }
+
+    #[test]
+    fn quoted_identifiers_keep_their_case() {
+        assert_eq!(
+            fingerprint_sql(r#"SELECT "UserId" FROM "Users""#),
+            r#"select "UserId" from "Users""#
+        );
+    }
+
+    #[test]
+    fn casts_stay_attached() {
+        assert_eq!(
+            fingerprint_sql("select '2024-01-01'::date, @p0::int4"),
+            "select $1::date, @p0::int4"
+        );
+    }
+
+    #[test]
+    fn comments_and_whitespace_are_dropped() {
+        assert_eq!(
+            fingerprint_sql("select a -- trailing\n  from /* block */ t"),
+            "select a from t"
+        );
+    }
+
+    #[test]
+    fn in_lists_collapse_whatever_their_length() {
+        let short = fingerprint_sql("select * from t where id in (1, 2)");
+        let long = fingerprint_sql("select * from t where id in (1, 2, 3, 4)");
+        assert_eq!(short, "select * from t where id in (...)");
+        assert_eq!(short, long);
+    }
+
+    #[test]
+    fn array_literals_collapse() {
+        assert_eq!(
+            fingerprint_sql("select * from t where id = any(array[@p0, @p1])"),
+            "select * from t where id = any(array [...])"
+        );
+    }
+
+    #[test]
+    fn values_rows_collapse_whatever_their_count() {
+        let one = fingerprint_sql("insert into t (a, b) values ($1, $2)");
+        let many = fingerprint_sql("insert into t (a, b) values ($1, $2), ($3, $4), ($5, $6)");
+        assert_eq!(one, "insert into t(a, b) values (...)");
+        assert_eq!(one, many);
+    }
+
+    #[test]
+    fn lists_with_expressions_are_kept() {
+        assert_eq!(
+            fingerprint_sql("insert into t (a, b) values ($1, now())"),
+            "insert into t(a, b) values ($1, now())"
+        );
+        assert_eq!(
+            fingerprint_sql("select * from t where id in (select id from u)"),
+            "select * from t where id in (select id from u)"
+        );
+    }
+
+    #[test]
+    fn batch_markers_are_ignored() {
+        assert_eq!(
+            fingerprint_sql("[-- Batch Command 1]\nselect 1"),
+            "select $1"
+        );
+    }
+
+    #[test]
+    fn primary_table_finds_the_target() {
+        assert_eq!(
+            primary_table("INSERT INTO public.orders (a) VALUES (1)").as_deref(),
+            Some("public.orders")
+        );
+        assert_eq!(
+            primary_table("update ONLY \"Users\" set a = 1").as_deref(),
+            Some("\"Users\"")
+        );
+        assert_eq!(
+            primary_table("select * from (select 1) s join items i on true").as_deref(),
+            Some("items")
+        );
+        assert_eq!(primary_table("select 1"), None);
+    }
+}
@@ -3,14 +3,14 @@ use ratatui::{
     text::{Line, Span, Text},
 };
 use sqlformat::{FormatOptions, QueryParams, format as sql_format};
+use std::sync::OnceLock;
 use syntect::{
     easy::HighlightLines,
-    highlighting::{Style as SynStyle, ThemeSet},
+    highlighting::{Style as SynStyle, Theme, ThemeSet},
     parsing::SyntaxSet,
     util::LinesWithEndings,
 };
 
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SqlSizeClass {
     Small,
@@ -36,29 +36,53 @@ pub fn syn_style_to_ratatui(span: SynStyle) -> Style {
     }
 }
 
+/// SQL formatting options derived from the effective config
+pub fn format_options() -> FormatOptions<'static> {
+    let config = crate::config::get();
+    FormatOptions {
+        indent: sqlformat::Indent::Spaces(config.sql_indent),
+        uppercase: Some(config.sql_uppercase),
+        lines_between_queries: 1,
+        ignore_case_convert: Some(vec![]),
+    }
+}
+
+static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+// Loading the syntax and theme definitions is expensive, so it happens once per process
+fn syntax_set() -> &'static SyntaxSet {
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    THEME.get_or_init(|| {
+        let mut ts = ThemeSet::load_defaults();
+        // Try a different theme - "base16-ocean.dark" tends to have better color contrast
+        let theme_name = if ts.themes.contains_key("base16-ocean.dark") {
+            "base16-ocean.dark"
+        } else if ts.themes.contains_key("Solarized (dark)") {
+            "Solarized (dark)"
+        } else if ts.themes.contains_key("Monokai") {
+            "Monokai"
+        } else {
+            "InspiredGitHub" // fallback
+        };
+        ts.themes.remove(theme_name).unwrap()
+    })
+}
+
 /// Highlight SQL using syntect and convert to ratatui Text
 pub fn highlight_sql(sql: String) -> Text<'static> {
-    let ps = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
+    let ps = syntax_set();
     let syntax = ps.find_syntax_by_extension("sql").unwrap();
 
-    // Try a different theme - "base16-ocean.dark" tends to have better color contrast
-    let theme_name = if ts.themes.contains_key("base16-ocean.dark") {
-        "base16-ocean.dark"
-    } else if ts.themes.contains_key("Solarized (dark)") {
-        "Solarized (dark)"
-    } else if ts.themes.contains_key("Monokai") {
-        "Monokai"
-    } else {
-        "InspiredGitHub" // fallback
-    };
-
-    let mut h = HighlightLines::new(syntax, &ts.themes[theme_name]);
+    let mut h = HighlightLines::new(syntax, theme());
 
     let mut lines = Vec::new();
 
     for line in LinesWithEndings::from(&sql) {
-        let ranges: Vec<(SynStyle, &str)> = h.highlight_line(line, &ps).unwrap();
+        let ranges: Vec<(SynStyle, &str)> = h.highlight_line(line, ps).unwrap();
         let mut spans = Vec::new();
 
         for (style, part) in ranges {
@@ -83,14 +107,9 @@ pub fn extract_batch_statement_at_cursor(statement: &str, cursor_pos: usize) ->
             if !current_batch_sql.trim().is_empty() {
                 batch_statements.push((line_count, current_batch_sql.clone()));
                 // Count lines for this batch (header + formatted lines + separator)
-                let format_options = FormatOptions {
-                    indent: sqlformat::Indent::Spaces(2),
-                    uppercase: Some(false),
-                    lines_between_queries: 1,
-                    ignore_case_convert: Some(vec![]),
-                };
+                let format_options = format_options();
                 let formatted_sql = sql_format(
-                    &current_batch_sql.trim(),
+                    current_batch_sql.trim(),
                     &QueryParams::None,
                     &format_options,
                 );
@@ -119,13 +138,8 @@ pub fn extract_batch_statement_at_cursor(statement: &str, cursor_pos: usize) ->
 
     // Find which batch the cursor is in
     for (start_line, batch_sql) in batch_statements.iter() {
-        let format_options = FormatOptions {
-            indent: sqlformat::Indent::Spaces(2),
-            uppercase: Some(false),
-            lines_between_queries: 1,
-            ignore_case_convert: Some(vec![]),
-        };
-        let formatted_sql = sql_format(&batch_sql.trim(), &QueryParams::None, &format_options);
+        let format_options = format_options();
+        let formatted_sql = sql_format(batch_sql.trim(), &QueryParams::None, &format_options);
 
         let batch_line_count = if formatted_sql.trim().is_empty() {
             batch_sql.lines().count().max(1)
@@ -167,6 +181,24 @@ pub fn sql_size_color(class: SqlSizeClass) -> Color {
     }
 }
 
+/// Background for a row count: neutral for small results, amber and orange for large ones.
+pub fn row_count_color(rows: u64) -> Color {
+    match rows {
+        0..=999 => Color::Rgb(70, 70, 90),
+        1000..=9999 => Color::Rgb(255, 193, 7), // Amber/Yellow
+        _ => Color::Rgb(255, 87, 34),           // Deep Orange
+    }
+}
+
+/// Compact count like 950, 12.5k or 1.2M.
+pub fn format_count(count: u64) -> String {
+    match count {
+        0..=9_999 => count.to_string(),
+        10_000..=999_999 => format!("{:.1}k", count as f64 / 1000.0),
+        _ => format!("{:.1}M", count as f64 / 1_000_000.0),
+    }
+}
+
 /// Represents a query type badge with styling information
 #[derive(Debug, Clone)]
 pub struct QueryBadge {
@@ -185,7 +217,9 @@ pub fn detect_query_badge(sql: &str) -> Option<QueryBadge> {
     }
 
     // Count queries (only for COUNT(*) or similar count queries)
-    if sql_trimmed.starts_with("select") && (sql_trimmed.contains("count(") || sql_trimmed.contains("count (")) {
+    if sql_trimmed.starts_with("select")
+        && (sql_trimmed.contains("count(") || sql_trimmed.contains("count ("))
+    {
         return Some(QueryBadge {
             label: "COUNT".to_string(),
             bg_color: Color::Rgb(245, 222, 179), // Beige
@@ -0,0 +1,230 @@
+use ratatui::style::Color;
+use std::{
+    io::{IsTerminal, Write},
+    sync::mpsc,
+};
+
+use crate::{
+    RequestGroup, SqlLogMessage,
+    config::{ColorChoice, OutputFormat},
+    format::{format_options, highlight_sql},
+    listener::IncomingLine,
+};
+
+/// Options for printing queries to stdout instead of running the TUI.
+#[derive(Debug, Clone, Copy)]
+pub struct HeadlessOptions {
+    pub format: OutputFormat,
+    pub color: ColorChoice,
+    pub formatted_sql: bool,
+}
+
+/// Print every received query to stdout until the channel closes or stdout goes away.
+/// Lines that fail to parse are reported on stderr.
+pub fn run_headless(
+    rx: mpsc::Receiver<IncomingLine>,
+    options: HeadlessOptions,
+) -> anyhow::Result<()> {
+    let use_color = match options.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => std::io::stdout().is_terminal(),
+    };
+    let mut stdout = std::io::stdout().lock();
+    // Sequence numbers as the TUI would assign them, so JSON output can be correlated
+    let mut next_id = 0;
+
+    for incoming in rx {
+        if incoming.line.trim().is_empty() {
+            continue;
+        }
+        let msg = match crate::parse_log_line(&incoming) {
+            Ok(mut msg) => {
+                msg.id = next_id;
+                next_id += 1;
+                msg
+            }
+            Err(e) => {
+                eprintln!("rejected line from {}: {}", incoming.source, e);
+                continue;
+            }
+        };
+        let output = match options.format {
+            OutputFormat::Json => serde_json::to_string(&msg)?,
+            OutputFormat::Text => format_text_line(&msg, options.formatted_sql, use_color),
+        };
+        // Stop quietly when the reading end of a pipe is closed
+        if writeln!(stdout, "{}", output).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// One query as "time duration method endpoint [source] sql"
+fn format_text_line(msg: &SqlLogMessage, formatted_sql: bool, use_color: bool) -> String {
+    let group = RequestGroup::from_message(msg, crate::config::GroupingMode::Endpoint);
+    let time = crate::ui::message_time(msg);
+    let duration = crate::format_duration(msg.duration);
+    // Formatted SQL starts on its own line below the query summary
+    let (separator, sql) = if formatted_sql {
+        let formatted = sqlformat::format(
+            &msg.statement,
+            &sqlformat::QueryParams::None,
+            &format_options(),
+        );
+        if formatted.trim().is_empty() {
+            ("\n", msg.statement.clone())
+        } else {
+            ("\n", formatted.trim_end().to_string())
+        }
+    } else {
+        (" ", minify_sql(&msg.statement))
+    };
+    let source = msg
+        .source
+        .as_ref()
+        .map(|source| format!(" [{}]", source))
+        .unwrap_or_default();
+    let error = if msg.is_error() {
+        format!(
+            " ERROR {}: {}",
+            msg.sqlstate.as_deref().unwrap_or("-"),
+            msg.error_message.as_deref().unwrap_or("(no message)")
+        )
+    } else {
+        String::new()
+    };
+
+    if !use_color {
+        return format!(
+            "{} {:>8} {} {}{}{}{}{}",
+            time, duration, group.http_method, group.endpoint, source, error, separator, sql
+        );
+    }
+
+    let (r, g, b) = crate::interpolate_color(msg.duration);
+    let error = if error.is_empty() {
+        error
+    } else {
+        format!("{}{}{}", ansi_fg(Color::Red), error, ANSI_RESET)
+    };
+    format!(
+        "{} {}{:>8}{} {}{}{} {}{}{}{}{}",
+        time,
+        ansi_fg(Color::Rgb(r, g, b)),
+        duration,
+        ANSI_RESET,
+        ansi_fg(crate::get_http_method_color(&group.http_method)),
+        group.http_method,
+        ANSI_RESET,
+        group.endpoint,
+        source,
+        error,
+        separator,
+        highlight_sql_ansi(&sql)
+    )
+}
+
+// Collapse all whitespace so a statement fits on one line. Line comments, such as the tags EF Core
+// adds with TagWith, become block comments first, or they would comment out the rest of the line.
+fn minify_sql(sql: &str) -> String {
+    block_line_comments(sql)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Rewrite "-- text" as "/* text */", leaving string literals and quoted identifiers alone
+fn block_line_comments(sql: &str) -> String {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut out = String::with_capacity(sql.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' || c == '"' {
+            // A backslash escapes the next character in E'...' strings
+            let backslash_escapes = c == '\'' && i > 0 && matches!(chars[i - 1], 'E' | 'e');
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if backslash_escapes && chars[i] == '\\' {
+                    2
+                } else {
+                    1
+                };
+            }
+            i = (i + 1).min(chars.len());
+            out.extend(&chars[start..i]);
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            let end = chars[i..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map_or(chars.len(), |offset| i + offset);
+            let comment: String = chars[i + 2..end].iter().collect();
+            out.push_str(&format!("/* {} */", comment.trim().replace("*/", "* /")));
+            i = end;
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
+    out
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+fn ansi_fg(color: Color) -> String {
+    match color {
+        Color::Rgb(r, g, b) => format!("\x1b[38;2;{};{};{}m", r, g, b),
+        _ => String::new(),
+    }
+}
+
+// Syntax highlight SQL with the TUI colours, rendered as ANSI escape sequences
+fn highlight_sql_ansi(sql: &str) -> String {
+    let mut output = String::new();
+    for line in highlight_sql(sql.to_string()).lines {
+        for span in line.spans {
+            match span.style.fg {
+                Some(color) => {
+                    output.push_str(&ansi_fg(color));
+                    output.push_str(&span.content);
+                    output.push_str(ANSI_RESET);
+                }
+                None => output.push_str(&span.content),
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_collapses_to_single_spaces() {
+        assert_eq!(
+            minify_sql("SELECT a,\n       b\n  FROM t\tWHERE x = 1"),
+            "SELECT a, b FROM t WHERE x = 1"
+        );
+    }
+
+    #[test]
+    fn line_comments_become_block_comments() {
+        assert_eq!(
+            minify_sql("-- GetOrders\n\nSELECT o.id\nFROM orders AS o -- trailing\nWHERE o.id = 1"),
+            "/* GetOrders */ SELECT o.id FROM orders AS o /* trailing */ WHERE o.id = 1"
+        );
+        assert_eq!(minify_sql("select 1 --*/ x"), "select 1 /* * / x */");
+    }
+
+    #[test]
+    fn dashes_in_quotes_are_not_comments() {
+        assert_eq!(
+            minify_sql("select '--a', \"--b\", E'\\'--c'\nfrom t"),
+            "select '--a', \"--b\", E'\\'--c' from t"
+        );
+    }
+}
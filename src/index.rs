@@ -0,0 +1,341 @@
+use chrono::{DateTime, Utc};
+use std::{
+    cell::OnceCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    time::Instant,
+};
+
+use crate::{
+    RequestGroup, SqlLogMessage,
+    config::GroupingMode,
+    stats::{NPlusOne, detect_n_plus_one},
+};
+
+/// Retained messages plus their request groups, kept up to date as messages arrive and are
+/// evicted so the UI never has to regroup the whole log.
+#[derive(Default)]
+pub struct MessageIndex {
+    // Keyed by arrival sequence number, so iteration is in arrival order
+    messages: BTreeMap<u64, Retained>,
+    groups: HashMap<RequestGroup, GroupEntry>,
+    mode: GroupingMode,
+    next_seq: u64,
+    // Bumped whenever messages or groups change, so views built from the index know when to
+    // rebuild
+    generation: u64,
+    sql_bytes: usize,
+    evicted: usize,
+    // Messages retention may drop, oldest first. Exempt ones are taken out as they are found, so
+    // they neither count towards the limits nor get walked again on every tick.
+    evictable: BTreeSet<u64>,
+    exempt_bytes: usize,
+    // Messages from this sequence number on have not been checked for exemption yet
+    unchecked_from: u64,
+}
+
+struct Retained {
+    message: SqlLogMessage,
+    received_at: Instant,
+}
+
+/// Limits on what the index keeps. Count and size are checked after every insert, age whenever
+/// `evict` runs.
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    pub max_entries: usize,
+    pub max_sql_bytes: Option<u64>,
+    pub max_age: Option<std::time::Duration>,
+}
+
+// Members of one group ordered by (time, seq), so the newest message is always last.
+// N+1 detection runs on first use and is reset whenever the members change.
+#[derive(Default)]
+struct GroupEntry {
+    members: BTreeSet<(DateTime<Utc>, u64)>,
+    n_plus_one: OnceCell<Option<NPlusOne>>,
+}
+
+impl MessageIndex {
+    pub fn new(mode: GroupingMode) -> Self {
+        MessageIndex {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// The grouping mode the groups are currently keyed on.
+    pub fn mode(&self) -> GroupingMode {
+        self.mode
+    }
+
+    /// Switch to another grouping mode, rebuilding the groups from the retained messages.
+    pub fn regroup(&mut self, mode: GroupingMode) {
+        self.mode = mode;
+        self.generation += 1;
+        self.groups.clear();
+        for (seq, retained) in &self.messages {
+            self.groups
+                .entry(RequestGroup::from_message(&retained.message, mode))
+                .or_default()
+                .members
+                .insert((retained.message.time, *seq));
+        }
+    }
+
+    /// Add a message, assigning it the next sequence number as its id.
+    pub fn insert(&mut self, mut msg: SqlLogMessage) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.generation += 1;
+        msg.id = seq;
+        let entry = self
+            .groups
+            .entry(RequestGroup::from_message(&msg, self.mode))
+            .or_default();
+        entry.members.insert((msg.time, seq));
+        entry.n_plus_one = OnceCell::new();
+        self.sql_bytes += msg.statement.len();
+        self.evictable.insert(seq);
+        self.messages.insert(
+            seq,
+            Retained {
+                message: msg,
+                received_at: Instant::now(),
+            },
+        );
+        seq
+    }
+
+    /// Drop the oldest messages until the retention limits are met, skipping messages for which
+    /// `is_exempt` returns true. Exempt messages do not count towards the limits. Only messages
+    /// that arrived since the last call are checked for exemption, so call `exemptions_changed`
+    /// whenever what `is_exempt` returns may change. Returns the number of evicted messages.
+    pub fn evict(
+        &mut self,
+        retention: &Retention,
+        is_exempt: impl Fn(&SqlLogMessage) -> bool,
+    ) -> usize {
+        for (seq, retained) in self.messages.range(self.unchecked_from..) {
+            if is_exempt(&retained.message) {
+                self.evictable.remove(seq);
+                self.exempt_bytes += retained.message.statement.len();
+            }
+        }
+        self.unchecked_from = self.next_seq;
+
+        let now = Instant::now();
+        let mut count = self.evictable.len();
+        let mut bytes = (self.sql_bytes - self.exempt_bytes) as u64;
+        let mut doomed = Vec::new();
+        for seq in &self.evictable {
+            let retained = &self.messages[seq];
+            let too_old = retention
+                .max_age
+                .is_some_and(|max_age| now.duration_since(retained.received_at) > max_age);
+            let over_budget = count > retention.max_entries
+                || retention.max_sql_bytes.is_some_and(|max| bytes > max);
+            // Everything after this one is newer, so nothing else has to go
+            if !too_old && !over_budget {
+                break;
+            }
+            doomed.push(*seq);
+            count -= 1;
+            bytes -= retained.message.statement.len() as u64;
+        }
+
+        for seq in &doomed {
+            if let Some(retained) = self.messages.remove(seq) {
+                self.unlink(*seq, &retained.message);
+            }
+        }
+        if !doomed.is_empty() {
+            self.evicted += doomed.len();
+            self.generation += 1;
+        }
+        doomed.len()
+    }
+
+    /// Have the next `evict` check every retained message for exemption again, e.g. after a pin
+    /// or bookmark was removed.
+    pub fn exemptions_changed(&mut self) {
+        self.evictable = self.messages.keys().copied().collect();
+        self.exempt_bytes = 0;
+        self.unchecked_from = 0;
+    }
+
+    /// Number of retained messages kept regardless of the retention limits.
+    pub fn exempt(&self) -> usize {
+        self.messages.len() - self.evictable.len()
+    }
+
+    /// Number of messages evicted by the retention limits since the last clear.
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    fn unlink(&mut self, seq: u64, msg: &SqlLogMessage) {
+        self.sql_bytes -= msg.statement.len();
+        self.evictable.remove(&seq);
+        let group = RequestGroup::from_message(msg, self.mode);
+        if let Some(entry) = self.groups.get_mut(&group) {
+            entry.members.remove(&(msg.time, seq));
+            entry.n_plus_one = OnceCell::new();
+            if entry.members.is_empty() {
+                self.groups.remove(&group);
+            }
+        }
+    }
+
+    // The sequence keeps counting, so ids are never reused within a session
+    pub fn clear(&mut self) {
+        self.generation += 1;
+        self.messages.clear();
+        self.groups.clear();
+        self.sql_bytes = 0;
+        self.evicted = 0;
+        self.evictable.clear();
+        self.exempt_bytes = 0;
+    }
+
+    /// Changes whenever a message is added or evicted, or the groups are rebuilt.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// A retained message by its id.
+    pub fn get(&self, id: u64) -> Option<&SqlLogMessage> {
+        self.messages.get(&id).map(|retained| &retained.message)
+    }
+
+    /// All retained messages in arrival order.
+    pub fn messages(&self) -> impl Iterator<Item = &SqlLogMessage> {
+        self.messages.values().map(|retained| &retained.message)
+    }
+
+    /// The largest N+1 burst among all retained messages of a group.
+    pub fn n_plus_one(&self, group: &RequestGroup) -> Option<&NPlusOne> {
+        let entry = self.groups.get(group)?;
+        entry
+            .n_plus_one
+            .get_or_init(|| {
+                let messages: Vec<&SqlLogMessage> = entry
+                    .members
+                    .iter()
+                    .filter_map(|(_, seq)| self.messages.get(seq).map(|retained| &retained.message))
+                    .collect();
+                detect_n_plus_one(&messages)
+            })
+            .as_ref()
+    }
+
+    /// Groups with their messages (newest first), pinned groups first and the rest ordered by
+    /// their most recent message.
+    pub fn groups(
+        &self,
+        pinned_groups: &HashSet<RequestGroup>,
+    ) -> Vec<(&RequestGroup, impl Iterator<Item = &SqlLogMessage>)> {
+        let mut groups: Vec<(&RequestGroup, &GroupEntry)> = self.groups.iter().collect();
+        groups.sort_by(|a, b| {
+            let a_pinned = pinned_groups.contains(a.0);
+            let b_pinned = pinned_groups.contains(b.0);
+            // Pinned groups always come first, then the most recent activity
+            b_pinned
+                .cmp(&a_pinned)
+                .then_with(|| b.1.latest().cmp(&a.1.latest()))
+                // Equal timestamps fall back to endpoint and method for stable sorting
+                .then_with(|| a.0.endpoint.cmp(&b.0.endpoint))
+                .then_with(|| a.0.http_method.cmp(&b.0.http_method))
+                .then_with(|| a.0.source.cmp(&b.0.source))
+                .then_with(|| a.0.request_id.cmp(&b.0.request_id))
+        });
+        groups
+            .into_iter()
+            .map(|(group, entry)| {
+                let messages = entry.members.iter().rev().filter_map(|(_, seq)| {
+                    self.messages.get(seq).map(|retained| &retained.message)
+                });
+                (group, messages)
+            })
+            .collect()
+    }
+}
+
+impl GroupEntry {
+    fn latest(&self) -> Option<DateTime<Utc>> {
+        self.members.last().map(|(time, _)| *time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(endpoint: &str) -> SqlLogMessage {
+        serde_json::from_value(serde_json::json!({
+            "statement": "select 1",
+            "duration": 1,
+            "timestamp": "2026-01-01T00:00:00Z",
+            "endpoint": endpoint,
+        }))
+        .unwrap()
+    }
+
+    fn retention(max_entries: usize) -> Retention {
+        Retention {
+            max_entries,
+            max_sql_bytes: None,
+            max_age: None,
+        }
+    }
+
+    fn endpoints(index: &MessageIndex) -> Vec<String> {
+        index
+            .messages()
+            .map(|msg| msg.endpoint.clone().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn oldest_messages_are_evicted_first() {
+        let mut index = MessageIndex::new(GroupingMode::Endpoint);
+        for endpoint in ["/a", "/b", "/c"] {
+            index.insert(message(endpoint));
+        }
+        assert_eq!(index.evict(&retention(2), |_| false), 1);
+        assert_eq!(endpoints(&index), ["/b", "/c"]);
+        assert_eq!(index.evicted(), 1);
+    }
+
+    #[test]
+    fn exempt_messages_do_not_count_towards_the_limit() {
+        let mut index = MessageIndex::new(GroupingMode::Endpoint);
+        let pinned = |msg: &SqlLogMessage| msg.endpoint.as_deref() == Some("/pinned");
+        for endpoint in ["/pinned", "/a", "/pinned", "/b", "/c"] {
+            index.insert(message(endpoint));
+        }
+        assert_eq!(index.evict(&retention(2), pinned), 1);
+        assert_eq!(endpoints(&index), ["/pinned", "/pinned", "/b", "/c"]);
+        assert_eq!(index.exempt(), 2);
+
+        index.insert(message("/d"));
+        assert_eq!(index.evict(&retention(2), pinned), 1);
+        assert_eq!(endpoints(&index), ["/pinned", "/pinned", "/c", "/d"]);
+    }
+
+    #[test]
+    fn unpinned_messages_are_evicted_after_exemptions_change() {
+        let mut index = MessageIndex::new(GroupingMode::Endpoint);
+        for endpoint in ["/pinned", "/a", "/b"] {
+            index.insert(message(endpoint));
+        }
+        index.evict(&retention(2), |msg| {
+            msg.endpoint.as_deref() == Some("/pinned")
+        });
+        assert_eq!(index.evict(&retention(2), |_| false), 0);
+
+        index.exemptions_changed();
+        assert_eq!(index.evict(&retention(2), |_| false), 1);
+        assert_eq!(endpoints(&index), ["/a", "/b"]);
+        assert_eq!(index.exempt(), 0);
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::mpsc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    net::TcpListener,
+};
+
+/// A raw line received from a logger, tagged with the connection it arrived on.
+#[derive(Debug, Clone)]
+pub struct IncomingLine {
+    pub source: String,
+    pub line: String,
+    // Set when the bytes were not valid UTF-8; `line` then holds a lossy copy for display
+    pub decode_error: Option<String>,
+}
+
+// Optional first line a logger can send to name itself, e.g. {"app_name":"OrdersApi"}
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HelloMessage {
+    app_name: String,
+}
+
+/// Bind a Unix domain socket that only the current user can connect to, replacing a stale
+/// socket file but refusing to take over one that another instance is still listening on.
+#[cfg(unix)]
+pub fn bind_unix(path: &std::path::Path) -> anyhow::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => anyhow::bail!("another instance is listening on {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                std::fs::remove_file(path)?
+            }
+            Err(e) => anyhow::bail!("cannot tell whether {} is in use: {}", path.display(), e),
+        }
+    }
+
+    // Bind inside a private directory and only move the socket into place once its permissions
+    // are restricted, so no other user can connect in between
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let staging = parent.join(format!(".pgquerymon-{}", std::process::id()));
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .map_err(|e| anyhow::anyhow!("failed to create {}: {}", staging.display(), e))?;
+    let staged = staging.join("socket");
+    let result = tokio::net::UnixListener::bind(&staged)
+        .map_err(|e| anyhow::anyhow!("failed to listen on {}: {}", path.display(), e))
+        .and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&staged, path)?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&staging);
+    result
+}
+
+/// Removes the socket file when dropped.
+#[cfg(unix)]
+pub struct SocketFileGuard(pub std::path::PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketFileGuard {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Accept Unix domain socket connections forever, handling every connection concurrently.
+#[cfg(unix)]
+pub async fn accept_unix(listener: tokio::net::UnixListener, tx: mpsc::Sender<IncomingLine>) {
+    // Unix peers are usually unnamed, so number the connections instead
+    let mut connection_id: u64 = 0;
+    let mut failing = false;
+    loop {
+        let socket = match listener.accept().await {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                accept_failed("unix socket", &e, &mut failing).await;
+                continue;
+            }
+        };
+        failing = false;
+        connection_id += 1;
+        tokio::spawn(handle_connection(
+            socket,
+            format!("unix#{}", connection_id),
+            tx.clone(),
+        ));
+    }
+}
+
+/// Accept TCP connections forever, handling every connection concurrently.
+pub async fn accept_tcp(listener: TcpListener, tx: mpsc::Sender<IncomingLine>) {
+    let mut failing = false;
+    loop {
+        let (socket, remote_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                accept_failed("TCP", &e, &mut failing).await;
+                continue;
+            }
+        };
+        failing = false;
+        // ip:port tells apart loggers on the same host; a hello message gives a name that also
+        // survives reconnects
+        tokio::spawn(handle_connection(
+            socket,
+            remote_addr.to_string(),
+            tx.clone(),
+        ));
+    }
+}
+
+// Errors such as running out of file descriptors (EMFILE) make accept fail again straight away, so
+// back off instead of spinning. Only the first error of a run is logged.
+async fn accept_failed(listener: &str, error: &std::io::Error, failing: &mut bool) {
+    if !*failing {
+        eprintln!("{} accept failed: {}", listener, error);
+        *failing = true;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+}
+
+// Forward every line of a connection until it closes or the receiver is gone. The source is the
+// remote address unless the first line is a hello message carrying an app name. Lines are read as
+// bytes, so a line that is not valid UTF-8 ends up in the rejected panel instead of ending the
+// connection.
+async fn handle_connection<R>(reader: R, remote: String, tx: mpsc::Sender<IncomingLine>)
+where
+    R: AsyncRead + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    let mut source = remote;
+    let mut first_line = true;
+
+    loop {
+        buffer.clear();
+        match reader.read_until(b'\n', &mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let bytes = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        let (line, decode_error) = match std::str::from_utf8(bytes) {
+            Ok(line) => (line.to_string(), None),
+            Err(e) => (
+                String::from_utf8_lossy(bytes).into_owned(),
+                Some(format!("line is not valid UTF-8: {}", e)),
+            ),
+        };
+
+        if first_line {
+            first_line = false;
+            if decode_error.is_none()
+                && let Ok(hello) = serde_json::from_str::<HelloMessage>(&line)
+            {
+                source = hello.app_name;
+                continue;
+            }
+        }
+        let incoming = IncomingLine {
+            source: source.clone(),
+            line,
+            decode_error,
+        };
+        if tx.send(incoming).is_err() {
+            break;
+        }
+    }
+}
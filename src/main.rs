@@ -8,43 +8,222 @@ use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
     style::{Color, Style},
-    text::Line,
+    text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
 };
 
-use sqlformat::{FormatOptions, QueryParams, format};
-use std::{collections::HashSet, sync::mpsc, time::Duration};
-use tokio::{
-    io::{AsyncBufReadExt, BufReader},
-    net::TcpListener,
-};
+use sqlformat::{QueryParams, format};
+use std::{borrow::Cow, collections::HashSet, sync::mpsc, time::Duration};
+use tokio::net::TcpListener;
 
 use arboard::Clipboard;
+use clap::Parser;
+mod config;
+mod filter;
+mod fingerprint;
 mod format;
+mod headless;
+mod index;
+mod listener;
+mod params;
+mod record;
+mod sort;
+mod stats;
 mod ui;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 struct SqlLogMessage {
     statement: String,
     duration: u64,
-    timestamp: String,                // or use chrono::DateTime if needed
+    timestamp: String,                // as sent by the logger
     endpoint: Option<String>,         // nullable field
     http_method: Option<String>,      // nullable field
     caller_namespace: Option<String>, // nullable field
     caller_class: Option<String>,     // nullable field
     caller_method: Option<String>,    // nullable field
-    uid: Option<String>,              // unique identifier for tracking selections
+    uid: Option<String>,              // identifier assigned by the logger, if any
+    // Values for the statement's placeholders, if the logger sends them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parameters: Option<params::Parameters>,
+    // Identify the HTTP request instance (or distributed trace) the query ran in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    span_id: Option<String>,
+    // Rows sent back by a query and rows changed by a command, when the logger reports them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rows_returned: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rows_affected: Option<u64>,
+    // Set when the statement failed, as reported by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sqlstate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    severity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+    // Internal sequence number assigned at ingestion. Unlike uid it is always present and never
+    // reused, so expansion, selection and bookmarks key on it. Exported as "seq" so it cannot
+    // clash with an id the logger sends, and never read from incoming lines.
+    #[serde(rename = "seq", skip_deserializing)]
+    id: u64,
+    // Connection the message arrived on (remote address or announced app name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    // Normalized statement shape, computed at ingestion
+    #[serde(skip)]
+    fingerprint: String,
+    // Parsed timestamp used for sorting and display, computed at ingestion. Falls back to the
+    // arrival time (and sets time_is_arrival) when the logger's timestamp cannot be parsed.
+    #[serde(skip)]
+    time: chrono::DateTime<chrono::Utc>,
+    #[serde(skip)]
+    time_is_arrival: bool,
+    // Fields this version does not know about, kept so newer logger versions still display
+    #[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+// A received line that could not be parsed as a SqlLogMessage
+#[derive(Debug, Clone)]
+struct RejectedLine {
+    source: String,
+    raw: String,
+    error: String,
+    received_at: chrono::DateTime<chrono::Local>,
 }
 
-// Group key for organizing messages by endpoint + HTTP method
+// Most recent rejected lines, plus a count of every rejection this session
+#[derive(Debug, Default)]
+struct RejectedLines {
+    lines: std::collections::VecDeque<RejectedLine>,
+    total: usize,
+}
+
+const MAX_REJECTED_LINES: usize = 200;
+
+impl RejectedLines {
+    fn push(&mut self, source: String, raw: String, error: String) {
+        self.total += 1;
+        self.lines.push_front(RejectedLine {
+            source,
+            raw,
+            error,
+            received_at: chrono::Local::now(),
+        });
+        self.lines.truncate(MAX_REJECTED_LINES);
+    }
+}
+
+// Group key for organizing messages by endpoint + HTTP method (and source or request instance
+// when enabled), or by whatever the current grouping mode keys on: fingerprint, table or source
+// in `endpoint`. When grouping by caller it is a node of the namespace → class → method tree.
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
 struct RequestGroup {
     endpoint: String,
     http_method: String,
+    source: Option<String>,
+    request_id: Option<String>,
+    caller: Option<CallerPath>,
+}
+
+// Path of a caller tree node. Class and method are None for the nodes above them.
+#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+struct CallerPath {
+    namespace: String,
+    class: Option<String>,
+    method: Option<String>,
+}
+
+impl CallerPath {
+    // 0 for namespaces, 1 for classes, 2 for methods
+    fn depth(&self) -> usize {
+        match (&self.class, &self.method) {
+            (None, _) => 0,
+            (Some(_), None) => 1,
+            (Some(_), Some(_)) => 2,
+        }
+    }
+
+    fn parent(&self) -> Option<CallerPath> {
+        match (&self.class, &self.method) {
+            (None, _) => None,
+            (Some(_), None) => Some(CallerPath {
+                class: None,
+                ..self.clone()
+            }),
+            (Some(_), Some(_)) => Some(CallerPath {
+                method: None,
+                ..self.clone()
+            }),
+        }
+    }
+}
+
+impl SqlLogMessage {
+    fn is_error(&self) -> bool {
+        self.error_message.is_some() || self.sqlstate.is_some()
+    }
+
+    // Rows touched either way. A command with RETURNING reports both counts for the same rows,
+    // so take the larger one rather than the sum.
+    fn rows(&self) -> Option<u64> {
+        self.rows_returned.max(self.rows_affected)
+    }
 }
 
 impl RequestGroup {
-    fn from_message(msg: &SqlLogMessage) -> Self {
+    fn from_message(msg: &SqlLogMessage, mode: config::GroupingMode) -> Self {
+        use config::GroupingMode;
+
+        let (endpoint, http_method) = match mode {
+            GroupingMode::Endpoint | GroupingMode::Request => {
+                return RequestGroup::endpoint_group(msg, mode == GroupingMode::Request);
+            }
+            // Caller mode ignores the request, messages are keyed on the method that ran them
+            GroupingMode::Caller => {
+                return RequestGroup::caller_node(CallerPath {
+                    namespace: msg
+                        .caller_namespace
+                        .clone()
+                        .unwrap_or("(no namespace)".to_string()),
+                    class: Some(msg.caller_class.clone().unwrap_or("(no class)".to_string())),
+                    method: Some(
+                        msg.caller_method
+                            .clone()
+                            .unwrap_or("(no method)".to_string()),
+                    ),
+                });
+            }
+            GroupingMode::Fingerprint => (msg.fingerprint.clone(), "QUERY"),
+            GroupingMode::Table => (
+                fingerprint::primary_table(&msg.statement).unwrap_or("(no table)".to_string()),
+                "TABLE",
+            ),
+            GroupingMode::Source => (
+                msg.source.clone().unwrap_or("(unknown source)".to_string()),
+                "SOURCE",
+            ),
+            // A single group that is listed without a header
+            GroupingMode::Tail => ("All queries".to_string(), "TAIL"),
+        };
+        RequestGroup {
+            endpoint,
+            http_method: http_method.to_string(),
+            source: None,
+            request_id: None,
+            caller: None,
+        }
+    }
+
+    // Endpoint and HTTP method, split per source and request instance when enabled
+    fn endpoint_group(msg: &SqlLogMessage, per_request: bool) -> Self {
         let (endpoint_str, http_method) = if msg.http_method.is_none() {
             // Show caller info when http_method is null
             let caller_info = match (&msg.caller_method, &msg.caller_class) {
@@ -60,107 +239,481 @@ impl RequestGroup {
                 msg.http_method.clone().unwrap_or("UNKNOWN".to_string()),
             )
         };
-        
+
         RequestGroup {
             endpoint: endpoint_str,
             http_method,
+            source: if config::get().group_by_source {
+                msg.source.clone()
+            } else {
+                None
+            },
+            // Messages without an id stay in their endpoint group
+            request_id: if per_request {
+                msg.request_id.clone().or_else(|| msg.trace_id.clone())
+            } else {
+                None
+            },
+            caller: None,
         }
     }
+
+    // Tree node named after the last part of its path
+    fn caller_node(path: CallerPath) -> Self {
+        let (kind, name) = match (&path.class, &path.method) {
+            (None, _) => ("NAMESPACE", path.namespace.clone()),
+            (Some(class), None) => ("CLASS", class.clone()),
+            (Some(_), Some(method)) => ("METHOD", method.clone()),
+        };
+        RequestGroup {
+            endpoint: name,
+            http_method: kind.to_string(),
+            source: None,
+            request_id: None,
+            caller: Some(path),
+        }
+    }
+
+    // Nesting level in the caller tree, 0 for every other group
+    fn depth(&self) -> usize {
+        self.caller.as_ref().map_or(0, CallerPath::depth)
+    }
+
+    // Namespace and class nodes list their methods rather than messages
+    fn is_branch(&self) -> bool {
+        self.caller
+            .as_ref()
+            .is_some_and(|path| path.method.is_none())
+    }
+
+    // Enclosing caller tree nodes, innermost first
+    fn ancestors(&self) -> Vec<RequestGroup> {
+        std::iter::successors(
+            self.caller.as_ref().and_then(CallerPath::parent),
+            CallerPath::parent,
+        )
+        .map(RequestGroup::caller_node)
+        .collect()
+    }
+
+    // The group itself or one of the tree nodes above it
+    fn is_within(&self, other: &RequestGroup) -> bool {
+        self == other || self.ancestors().contains(other)
+    }
+}
+
+// Groups and the messages in them that pass the current filter, borrowed from the index. In
+// caller mode the namespace and class nodes are added in tree order, each holding the messages
+// of all methods below it.
+struct GroupedLogMessages<'a> {
+    log_index: &'a index::MessageIndex,
+    mode: config::GroupingMode,
+    groups: Vec<(Cow<'a, RequestGroup>, Vec<&'a SqlLogMessage>)>,
+}
+
+// The filtered and sorted groups of the last `from_index`, held as message ids so they outlive the
+// borrow of the index. They are reused as long as everything they were built from is unchanged.
+#[derive(Default)]
+struct GroupCache {
+    key: Option<GroupCacheKey>,
+    groups: Vec<(RequestGroup, Vec<u64>)>,
 }
 
-// Grouped data structure
-struct GroupedLogMessages {
-    groups: Vec<(RequestGroup, Vec<SqlLogMessage>)>,
+#[derive(PartialEq)]
+struct GroupCacheKey {
+    index_generation: u64,
+    pins_revision: u64,
+    filter_text: String,
+    sort: sort::GroupSort,
 }
 
-impl GroupedLogMessages {
-    fn from_messages(messages: &[SqlLogMessage], pinned_groups: &HashSet<RequestGroup>) -> Self {
-        let mut group_map: std::collections::HashMap<RequestGroup, Vec<SqlLogMessage>> = 
-            std::collections::HashMap::new();
-            
-        // Group messages by RequestGroup
-        for msg in messages {
-            let group = RequestGroup::from_message(msg);
-            group_map.entry(group).or_insert_with(Vec::new).push(msg.clone());
+impl<'a> GroupedLogMessages<'a> {
+    fn from_index(
+        log_index: &'a index::MessageIndex,
+        cache: &'a mut GroupCache,
+        pinned_groups: &HashSet<RequestGroup>,
+        pins_revision: u64,
+        filter: &filter::Filter,
+        filter_text: &str,
+        sort: sort::GroupSort,
+    ) -> Self {
+        // The filter is parsed from filter_text, so the text stands in for it
+        let fresh = cache.key.as_ref().is_some_and(|key| {
+            key.index_generation == log_index.generation()
+                && key.pins_revision == pins_revision
+                && key.filter_text == filter_text
+                && key.sort == sort
+        });
+        if !fresh {
+            cache.groups = filter_and_sort(log_index, pinned_groups, filter, sort)
+                .into_iter()
+                .map(|(group, messages)| {
+                    (
+                        group.into_owned(),
+                        messages.iter().map(|msg| msg.id).collect(),
+                    )
+                })
+                .collect();
+            cache.key = Some(GroupCacheKey {
+                index_generation: log_index.generation(),
+                pins_revision,
+                filter_text: filter_text.to_string(),
+                sort,
+            });
         }
-        
-        // Convert to ordered vector, sorted by most recent message in each group
-        let mut groups: Vec<(RequestGroup, Vec<SqlLogMessage>)> = group_map.into_iter().collect();
-        groups.sort_by(|a, b| {
-            // Pinned groups always come first
-            let a_pinned = pinned_groups.contains(&a.0);
-            let b_pinned = pinned_groups.contains(&b.0);
-            
-            match (a_pinned, b_pinned) {
-                (true, false) => std::cmp::Ordering::Less,  // a is pinned, b is not
-                (false, true) => std::cmp::Ordering::Greater, // b is pinned, a is not
-                _ => {
-                    // Both pinned or both not pinned, sort by timestamp
-                    let a_latest = a.1.iter().map(|msg| &msg.timestamp).max();
-                    let b_latest = b.1.iter().map(|msg| &msg.timestamp).max();
-                    let timestamp_cmp = b_latest.cmp(&a_latest); // Most recent first
-                    
-                    // If timestamps are equal, use endpoint and method for stable sorting
-                    if timestamp_cmp == std::cmp::Ordering::Equal {
-                        let endpoint_cmp = a.0.endpoint.cmp(&b.0.endpoint);
-                        if endpoint_cmp == std::cmp::Ordering::Equal {
-                            a.0.http_method.cmp(&b.0.http_method)
-                        } else {
-                            endpoint_cmp
-                        }
-                    } else {
-                        timestamp_cmp
-                    }
-                }
+        let groups = cache
+            .groups
+            .iter()
+            .map(|(group, ids)| {
+                let messages = ids.iter().filter_map(|id| log_index.get(*id)).collect();
+                (Cow::Borrowed(group), messages)
+            })
+            .collect();
+        GroupedLogMessages {
+            log_index,
+            mode: log_index.mode(),
+            groups,
+        }
+    }
+
+    fn n_plus_one(&self, group: &RequestGroup) -> Option<&'a stats::NPlusOne> {
+        self.log_index.n_plus_one(group)
+    }
+
+    // The first failed query after `current` in display order, wrapping around at the end
+    fn next_error(&self, current: Option<&FlatNavigationItem>) -> Option<(RequestGroup, u64)> {
+        let messages: Vec<(&RequestGroup, &SqlLogMessage)> = self
+            .groups
+            .iter()
+            .filter(|(group, _)| !group.is_branch())
+            .flat_map(|(group, messages)| messages.iter().map(move |msg| (group.as_ref(), *msg)))
+            .collect();
+        let start = match current {
+            Some(FlatNavigationItem::Message(current)) => messages
+                .iter()
+                .position(|(_, msg)| msg.id == current.id)
+                .map_or(0, |index| index + 1),
+            Some(FlatNavigationItem::GroupHeader(current, _)) => messages
+                .iter()
+                .position(|(group, _)| group.is_within(current))
+                .unwrap_or(0),
+            None => 0,
+        };
+        (start..messages.len())
+            .chain(0..start)
+            .map(|index| messages[index])
+            .find(|(_, msg)| msg.is_error())
+            .map(|(group, msg)| (group.clone(), msg.id))
+    }
+}
+
+// The groups with messages passing the filter, sorted, plus the caller tree nodes in caller mode
+fn filter_and_sort<'a>(
+    log_index: &'a index::MessageIndex,
+    pinned_groups: &HashSet<RequestGroup>,
+    filter: &filter::Filter,
+    sort: sort::GroupSort,
+) -> Vec<(Cow<'a, RequestGroup>, Vec<&'a SqlLogMessage>)> {
+    let mut groups: Vec<(Cow<'a, RequestGroup>, Vec<&'a SqlLogMessage>)> = log_index
+        .groups(pinned_groups)
+        .into_iter()
+        .filter_map(|(group, messages)| {
+            let mut messages: Vec<&SqlLogMessage> =
+                messages.filter(|msg| filter.matches(msg)).collect();
+            sort.sort_messages(&mut messages);
+            // Skip groups with no matching messages
+            (!messages.is_empty()).then_some((Cow::Borrowed(group), messages))
+        })
+        .collect();
+    // Stable, so ties keep the index order of pinned first and most recent activity
+    groups.sort_by(|a, b| compare_groups(a, b, sort, pinned_groups));
+    if log_index.mode() == config::GroupingMode::Caller {
+        build_caller_tree(groups, sort, pinned_groups)
+    } else {
+        groups
+    }
+}
+
+// Pinned groups first, then the chosen sort on the groups' messages
+fn compare_groups(
+    (a, a_messages): &(Cow<RequestGroup>, Vec<&SqlLogMessage>),
+    (b, b_messages): &(Cow<RequestGroup>, Vec<&SqlLogMessage>),
+    sort: sort::GroupSort,
+    pinned_groups: &HashSet<RequestGroup>,
+) -> std::cmp::Ordering {
+    pinned_groups
+        .contains(b.as_ref())
+        .cmp(&pinned_groups.contains(a.as_ref()))
+        .then_with(|| sort.compare_groups((a, a_messages), (b, b_messages)))
+}
+
+// Put namespace and class nodes above the method groups. Leaves arrive already sorted; nodes are
+// sorted among their siblings on the messages of all methods below them.
+fn build_caller_tree<'a>(
+    leaves: Vec<(Cow<'a, RequestGroup>, Vec<&'a SqlLogMessage>)>,
+    sort: sort::GroupSort,
+    pinned_groups: &HashSet<RequestGroup>,
+) -> Vec<(Cow<'a, RequestGroup>, Vec<&'a SqlLogMessage>)> {
+    // Class nodes with the indices of their method leaves
+    type Classes = Vec<(RequestGroup, Vec<usize>)>;
+    // namespace -> class -> leaves, each level in order of first appearance
+    let mut tree: Vec<(RequestGroup, Classes)> = Vec::new();
+    for (index, (leaf, _)) in leaves.iter().enumerate() {
+        let ancestors = leaf.ancestors();
+        let [class, namespace] = ancestors.as_slice() else {
+            continue;
+        };
+        let classes = match tree.iter().position(|(node, _)| node == namespace) {
+            Some(position) => &mut tree[position].1,
+            None => {
+                tree.push((namespace.clone(), Vec::new()));
+                &mut tree.last_mut().unwrap().1
             }
-        });
-        
-        GroupedLogMessages { groups }
+        };
+        match classes.iter().position(|(node, _)| node == class) {
+            Some(position) => classes[position].1.push(index),
+            None => classes.push((class.clone(), vec![index])),
+        }
     }
-    
-    #[allow(dead_code)]
-    fn total_item_count(&self) -> usize {
-        self.groups.iter().map(|(_, messages)| messages.len()).sum()
+
+    let messages_of = |members: &[usize]| -> Vec<&'a SqlLogMessage> {
+        members
+            .iter()
+            .flat_map(|&index| leaves[index].1.iter().copied())
+            .collect()
+    };
+    // Each node paired with its messages and the leaves below it
+    let mut namespaces: Vec<_> = tree
+        .into_iter()
+        .map(|(namespace, classes)| {
+            let mut classes: Vec<_> = classes
+                .into_iter()
+                .map(|(class, members)| ((Cow::Owned(class), messages_of(&members)), members))
+                .collect();
+            classes.sort_by(|(a, _), (b, _)| compare_groups(a, b, sort, pinned_groups));
+            let members: Vec<usize> = classes
+                .iter()
+                .flat_map(|(_, members)| members.iter().copied())
+                .collect();
+            ((Cow::Owned(namespace), messages_of(&members)), classes)
+        })
+        .collect();
+    namespaces.sort_by(|(a, _), (b, _)| compare_groups(a, b, sort, pinned_groups));
+
+    let mut groups = Vec::new();
+    for (namespace, classes) in namespaces {
+        groups.push(namespace);
+        for (class, members) in classes {
+            groups.push(class);
+            groups.extend(members.iter().map(|&index| leaves[index].clone()));
+        }
     }
+    groups
+}
+
+// Whether the message's group, or a caller tree node above it, is pinned in the given mode
+fn is_pinned(
+    msg: &SqlLogMessage,
+    mode: config::GroupingMode,
+    pinned_groups: &HashSet<RequestGroup>,
+) -> bool {
+    if pinned_groups.is_empty() {
+        return false;
+    }
+    let group = RequestGroup::from_message(msg, mode);
+    pinned_groups.contains(&group)
+        || group
+            .ancestors()
+            .iter()
+            .any(|ancestor| pinned_groups.contains(ancestor))
+}
+
+// Expansion, pins and selection of a grouping mode while another one is shown
+#[derive(Default)]
+struct ParkedMode {
+    expanded_groups: HashSet<RequestGroup>,
+    pinned_groups: HashSet<RequestGroup>,
+    selected: Option<usize>,
+    selected_id: Option<u64>,
+    scroll_offset: usize,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let (tx, rx) = mpsc::channel::<String>();
-
-    // Spawn TCP listener thread
-    tokio::spawn(async move {
-        let listener = TcpListener::bind("localhost:6000").await.unwrap();
-        loop {
-            let (socket, _) = listener.accept().await.unwrap();
-            let reader = BufReader::new(socket);
-            let mut lines = reader.lines();
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                if tx.send(line).is_err() {
-                    break;
-                }
-            }
+    let cli = config::Cli::parse();
+    config::init(config::Config::load(&cli)?);
+    let config = config::get();
+
+    let (tx, rx) = mpsc::channel::<listener::IncomingLine>();
+
+    // Replay mode feeds a recording through the channel instead of listening
+    if let Some(config::Command::Replay {
+        file,
+        speed,
+        instant,
+    }) = &cli.command
+    {
+        if !(speed.is_finite() && *speed > 0.0) {
+            anyhow::bail!("--speed must be a number greater than zero");
         }
-    });
+        if cli.record.is_some() {
+            anyhow::bail!(
+                "--record cannot be combined with replay; the recording already holds the session"
+            );
+        }
+        let pace = if *instant {
+            record::ReplayPace::Instant
+        } else {
+            record::ReplayPace::Original { speed: *speed }
+        };
+        record::spawn_replay(file, pace, tx)?;
+        return run_frontend(&cli, rx);
+    }
 
-    // Start TUI loop
-    run_tui(rx)?;
-    Ok(())
+    // Record raw lines on their way to the TUI when requested
+    let tx = match &cli.record {
+        Some(path) => record::spawn_recorder(path, tx)?,
+        None => tx,
+    };
+
+    // Bind before entering the TUI so address errors are reported on the plain terminal
+    if config.tcp_enabled {
+        let listener = TcpListener::bind(&config.listen)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to listen on {}: {}", config.listen, e))?;
+
+        // Spawn TCP listener task; every connection is handled concurrently
+        tokio::spawn(listener::accept_tcp(listener, tx.clone()));
+    }
+
+    // Optional Unix domain socket listener, removed again on exit
+    #[cfg(unix)]
+    let _socket_guard = match &config.socket {
+        Some(path) => {
+            let listener = listener::bind_unix(path)?;
+            tokio::spawn(listener::accept_unix(listener, tx.clone()));
+            Some(listener::SocketFileGuard(path.clone()))
+        }
+        None => None,
+    };
+    drop(tx);
+
+    run_frontend(&cli, rx)
 }
 
-fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
+// Start the TUI loop, or print to stdout when running without a terminal UI
+fn run_frontend(
+    cli: &config::Cli,
+    rx: mpsc::Receiver<listener::IncomingLine>,
+) -> anyhow::Result<()> {
+    if cli.no_tui {
+        let options = headless::HeadlessOptions {
+            format: cli.format,
+            color: cli.color,
+            formatted_sql: cli.formatted_sql,
+        };
+        headless::run_headless(rx, options)
+    } else {
+        run_tui(rx)
+    }
+}
+
+fn run_tui(rx: mpsc::Receiver<listener::IncomingLine>) -> anyhow::Result<()> {
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut log_lines: Vec<SqlLogMessage> = vec![];
-    let mut log_buffer: Vec<String> = vec![]; // Buffer for new logs during scrollmode
-    let mut expanded_uids: HashSet<String> = HashSet::new();
+    let result = run_app(&mut terminal, rx);
+
+    // Clean up terminal, also when the app loop returned an error
+    disable_raw_mode()?;
+    execute!(std::io::stdout(), LeaveAlternateScreen)?;
+    result
+}
+
+// Parse a received line into a message tagged with the connection it came from
+fn parse_log_line(incoming: &listener::IncomingLine) -> Result<SqlLogMessage, String> {
+    if let Some(error) = &incoming.decode_error {
+        return Err(error.clone());
+    }
+    let mut msg: SqlLogMessage = serde_json::from_str(&incoming.line).map_err(|e| e.to_string())?;
+    // "seq" is where the sequence number is exported, so a logger's own would be written twice
+    msg.extra.remove("seq");
+    if msg.source.is_none() {
+        msg.source = Some(incoming.source.clone());
+    }
+    msg.fingerprint = fingerprint::fingerprint_sql(&msg.statement);
+    match parse_timestamp(&msg.timestamp) {
+        Some(time) => msg.time = time,
+        None => {
+            msg.time = chrono::Utc::now();
+            msg.time_is_arrival = true;
+        }
+    }
+    Ok(msg)
+}
+
+// RFC 3339 with any offset, or a date and time without offset, which is taken as local time
+fn parse_timestamp(timestamp: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+
+    let timestamp = timestamp.trim();
+    if let Ok(dt) = timestamp.parse::<chrono::DateTime<chrono::FixedOffset>>() {
+        return Some(dt.to_utc());
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(timestamp, format).ok())
+        .and_then(|naive| chrono::Local.from_local_datetime(&naive).earliest())
+        .map(|dt| dt.to_utc())
+}
+
+// Parse a received line and append it to the log. Lines that fail to parse are recorded as
+// rejected instead of ending the session. Returns true when a message was added. Retention limits
+// are applied separately by the caller.
+fn ingest_line(
+    incoming: listener::IncomingLine,
+    log_index: &mut index::MessageIndex,
+    rejected_lines: &mut RejectedLines,
+) -> bool {
+    if incoming.line.trim().is_empty() {
+        return false;
+    }
+    match parse_log_line(&incoming) {
+        Ok(msg) => {
+            log_index.insert(msg);
+            true
+        }
+        Err(e) => {
+            rejected_lines.push(incoming.source, incoming.line, e);
+            false
+        }
+    }
+}
+
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    rx: mpsc::Receiver<listener::IncomingLine>,
+) -> anyhow::Result<()> {
+    let config = config::get();
+    let mut log_index = index::MessageIndex::new(config.grouping);
+    let mut log_buffer: Vec<listener::IncomingLine> = vec![]; // Buffer for new logs during scrollmode
+    let mut expanded_ids: HashSet<u64> = HashSet::new();
     let mut expanded_groups: HashSet<RequestGroup> = HashSet::new(); // Track expanded groups
     let mut pinned_groups: HashSet<RequestGroup> = HashSet::new(); // Track pinned groups
+    let mut pins_revision: u64 = 0; // Bumped whenever pinned_groups changes
+    let mut bookmarked_ids: HashSet<u64> = HashSet::new(); // Exempt from retention like pinned groups
+    // State of the grouping modes not currently shown, restored when `g` comes back to them
+    let mut parked_modes: std::collections::HashMap<config::GroupingMode, ParkedMode> =
+        std::collections::HashMap::new();
+    let retention = index::Retention {
+        max_entries: config.max_entries,
+        max_sql_bytes: config.max_sql_bytes,
+        max_age: config.max_age,
+    };
+    let mut last_retention_check = std::time::Instant::now();
     let mut list_state = ListState::default();
     list_state.select(Some(1)); // Start at index 1 to account for padding line
 
@@ -183,31 +736,72 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
 
     // Filter state
     let mut filter_text = String::new();
+    // Last filter that parsed; stays active while the text has an error
+    let mut filter = filter::Filter::All;
+    let mut filter_error: Option<String> = None;
     let mut filter_focused = false;
 
+    // Order of groups and of the messages within them
+    let mut group_sort = sort::GroupSort::default();
+    let mut group_cache = GroupCache::default();
+
     // Help screen state
     let mut help_screen_visible = false;
 
-    // UID-based selection tracking
-    let mut selected_uid: Option<String> = None;
+    // Fingerprint statistics screen state
+    let mut stats_screen_visible = false;
+    let mut stats_selected: usize = 0;
+
+    // Dashboard screen state
+    let mut dashboard_visible = false;
+
+    // Timeline screen state: the group being shown and the selected query
+    let mut timeline_group: Option<RequestGroup> = None;
+    let mut timeline_selected: usize = 0;
+
+    // Rejected lines panel state
+    let mut rejected_lines = RejectedLines::default();
+    let mut rejected_panel_visible = false;
+    let mut rejected_panel_scroll: u16 = 0;
+
+    // ID-based selection tracking
+    let mut selected_id: Option<u64> = None;
 
     // Track the last known list height for paging
     let mut last_list_height = 10usize;
+    // Width of expanded SQL lines, so scroll handlers hit the same render cache entries
+    let mut last_sql_width = 80usize;
+    let mut render_cache = ui::RenderCache::default();
     loop {
-        // Store current selection UID before processing new logs
-        if let Some(selected) = list_state.selected() {
-            if selected > 0 {
-                let actual_index = selected - 1;
-                let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-                if actual_index < flat_items.len() {
-                    match &flat_items[actual_index] {
-                        FlatNavigationItem::Message(msg) => {
-                            selected_uid = msg.uid.clone();
-                        }
-                        FlatNavigationItem::GroupHeader(_) => {
-                            // Group headers don't have UIDs, keep the current selection
-                        }
+        let incoming_lines: Vec<listener::IncomingLine> = rx.try_iter().collect();
+        // Age-based retention is checked once a second, even when nothing arrives
+        let retention_due = last_retention_check.elapsed() >= Duration::from_secs(1);
+        let list_will_change =
+            !scroll_mode && (!incoming_lines.is_empty() || !log_buffer.is_empty() || retention_due);
+
+        // Store current selection ID before processing new logs
+        if list_will_change
+            && let Some(selected) = list_state.selected()
+            && selected > 0
+        {
+            let actual_index = selected - 1;
+            let grouped_messages = GroupedLogMessages::from_index(
+                &log_index,
+                &mut group_cache,
+                &pinned_groups,
+                pins_revision,
+                &filter,
+                &filter_text,
+                group_sort,
+            );
+            let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups);
+            if actual_index < flat_items.len() {
+                match &flat_items[actual_index] {
+                    FlatNavigationItem::Message(msg) => {
+                        selected_id = Some(msg.id);
+                    }
+                    FlatNavigationItem::GroupHeader(..) => {
+                        // Group headers don't have IDs, keep the current selection
                     }
                 }
             }
@@ -215,80 +809,71 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
 
         // Check for new logs
         let mut new_logs_received = false;
-        while let Ok(line) = rx.try_recv() {
+        for line in incoming_lines {
             if scroll_mode {
                 log_buffer.push(line);
-            } else {
-                let mut msg: SqlLogMessage = serde_json::from_str(&line)?;
-                // Generate UID if not present
-                if msg.uid.is_none() {
-                    msg.uid = Some(format!("{}-{}", msg.timestamp, log_lines.len()));
-                }
-                log_lines.push(msg);
-                if log_lines.len() > 1000 {
-                    log_lines.remove(0);
-                }
+            } else if ingest_line(line, &mut log_index, &mut rejected_lines) {
                 new_logs_received = true;
             }
         }
         // If scroll_mode was just exited, flush buffer
         if !scroll_mode && !log_buffer.is_empty() {
             for line in log_buffer.drain(..) {
-                let mut msg: SqlLogMessage = serde_json::from_str(&line)?;
-                if msg.uid.is_none() {
-                    msg.uid = Some(format!("{}-{}", msg.timestamp, log_lines.len()));
-                }
-                log_lines.push(msg);
-                if log_lines.len() > 1000 {
-                    log_lines.remove(0);
+                if ingest_line(line, &mut log_index, &mut rejected_lines) {
+                    new_logs_received = true;
                 }
+            }
+        }
+
+        // Apply retention limits; pinned groups (in any grouping mode) and bookmarked queries are
+        // never evicted. Like new logs, eviction waits until scroll mode ends.
+        if !scroll_mode && (new_logs_received || retention_due) {
+            last_retention_check = std::time::Instant::now();
+            let mode = log_index.mode();
+            let evicted = log_index.evict(&retention, |msg| {
+                bookmarked_ids.contains(&msg.id)
+                    || is_pinned(msg, mode, &pinned_groups)
+                    || parked_modes
+                        .iter()
+                        .any(|(mode, parked)| is_pinned(msg, *mode, &parked.pinned_groups))
+            });
+            if evicted > 0 {
                 new_logs_received = true;
             }
         }
 
-        // Restore selection based on UID after new logs arrive
+        // Restore selection based on ID after new logs arrive
         // Only do this if scroll_mode is NOT active, so scroll mode selection stays stable
-        if new_logs_received && selected_uid.is_some() {
-            if !scroll_mode {
-                let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-                if let Some(uid) = &selected_uid {
-                    // Find the item with the matching UID in the flattened structure
-                    let mut found_index = None;
-                    for (index, item) in flat_items.iter().enumerate() {
-                        if let FlatNavigationItem::Message(msg) = item {
-                            if msg.uid.as_ref() == Some(uid) {
-                                found_index = Some(index);
-                                list_state.select(Some(index + 1)); // +1 for padding line
-                                break;
-                            }
-                        }
-                    }
-                    // Adjust main_scroll_offset to keep selected item at same visible position
-                    if let Some(found_index) = found_index {
-                        // If the previous selected index was known, keep the same relative position
-                        // Otherwise, keep the selected item visible
-                        let visible_height = last_list_height.saturating_sub(2); // minus border/padding
-                        if found_index < main_scroll_offset {
-                            main_scroll_offset = found_index;
-                        } else if found_index >= main_scroll_offset + visible_height {
-                            main_scroll_offset = found_index.saturating_sub(visible_height - 1);
-                        }
-                        // Clamp scroll offset to valid range
-                        let max_scroll = flat_items.len().saturating_sub(visible_height);
-                        if main_scroll_offset > max_scroll {
-                            main_scroll_offset = max_scroll;
-                        }
+        if new_logs_received && selected_id.is_some() && !scroll_mode {
+            let grouped_messages = GroupedLogMessages::from_index(
+                &log_index,
+                &mut group_cache,
+                &pinned_groups,
+                pins_revision,
+                &filter,
+                &filter_text,
+                group_sort,
+            );
+            let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups);
+            if let Some(id) = selected_id {
+                // Find the item with the matching ID in the flattened structure. The viewport
+                // scrolls to keep it visible when the list is drawn.
+                for (index, item) in flat_items.iter().enumerate() {
+                    if let FlatNavigationItem::Message(msg) = item
+                        && msg.id == id
+                    {
+                        list_state.select(Some(index + 1)); // +1 for padding line
+                        break;
                     }
                 }
             }
         }
 
         // Check and clear flash state if duration has passed
-        if let Some((_, flash_time)) = copy_flash_state {
-            if flash_time.elapsed() > COPY_FLASH_DURATION {
-                copy_flash_state = None;
-            }
+        if let Some((_, flash_time)) = copy_flash_state
+            && flash_time.elapsed() > COPY_FLASH_DURATION
+        {
+            copy_flash_state = None;
         }
 
         // Draw UI
@@ -309,8 +894,19 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
                     Line::from("  Enter      Toggle expand/collapse"),
                     Line::from("  l          Enter scroll mode"),
                     Line::from("  t          Pin/unpin group"),
+                    Line::from("  b          Bookmark query (kept by retention)"),
+                    Line::from("  p          Show parameter values inlined in the SQL"),
+                    Line::from("  e          Jump to the next failed query"),
+                    Line::from("  w          Show a timeline of the selected group"),
+                    Line::from("  g          Cycle grouping: endpoint, request, caller, fingerprint,"),
+                    Line::from("             table, source, tail"),
+                    Line::from("  o          Cycle sort: recent, total time, max time, count, name"),
+                    Line::from("  O          Reverse the sort order"),
                     Line::from("  f          Focus filter"),
                     Line::from("  y          Copy SQL (in scroll mode)"),
+                    Line::from("  s          Show query fingerprint statistics"),
+                    Line::from("  D          Show the dashboard"),
+                    Line::from("  r          Show rejected log lines"),
                     Line::from("  h          Show this help"),
                     Line::from(""),
                     Line::from("Scroll Mode:"),
@@ -322,8 +918,24 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
                     Line::from("  y          Copy current SQL"),
                     Line::from("  Esc        Exit scroll mode & collapse"),
                     Line::from(""),
+                    Line::from("Statistics:"),
+                    Line::from("  j / k      Select fingerprint"),
+                    Line::from("  s / Esc    Close statistics"),
+                    Line::from(""),
+                    Line::from("Rejected Lines:"),
+                    Line::from("  j / k      Scroll"),
+                    Line::from("  c          Clear rejected lines"),
+                    Line::from("  r / Esc    Close panel"),
+                    Line::from(""),
                     Line::from("Filter Mode:"),
-                    Line::from("  Type       Filter by endpoint/method/class"),
+                    Line::from("  Type       Filter by endpoint/method/class/source"),
+                    Line::from(
+                        "  sql:text   Field match (sql, method, endpoint, class, caller, source, request)",
+                    ),
+                    Line::from("  sql~/re/   Field matches a regex"),
+                    Line::from("  dur>200    Duration in ms (>, >=, <, <=, =; 1s for seconds)"),
+                    Line::from("  rows>1000  Rows returned (affected>N for rows changed)"),
+                    Line::from("  -term      Negate (also NOT), combine with OR and ( )"),
                     Line::from("  Enter/Esc  Exit filter mode"),
                     Line::from(""),
                     Line::from("General:"),
@@ -343,6 +955,34 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
                     .style(Style::default().fg(Color::White));
 
                 f.render_widget(help_paragraph, f.size());
+            } else if rejected_panel_visible {
+                ui::render_rejected_panel(f, f.size(), &rejected_lines, rejected_panel_scroll);
+            } else if let Some(group) = &timeline_group {
+                let grouped_messages = GroupedLogMessages::from_index(
+                    &log_index,
+                    &mut group_cache,
+                    &pinned_groups,
+                    pins_revision,
+                    &filter,
+                    &filter_text,
+                    group_sort,
+                );
+                let mut messages: Vec<&SqlLogMessage> = grouped_messages
+                    .groups
+                    .iter()
+                    .find(|(candidate, _)| candidate.as_ref() == group)
+                    .map(|(_, messages)| messages.clone())
+                    .unwrap_or_default();
+                messages.sort_by_key(|msg| (msg.time, msg.id));
+                timeline_selected = timeline_selected.min(messages.len().saturating_sub(1));
+                ui::render_timeline_screen(f, f.size(), group, &messages, timeline_selected);
+            } else if dashboard_visible {
+                let dashboard = stats::dashboard(log_index.messages(), config.dashboard_window);
+                ui::render_dashboard_screen(f, f.size(), &dashboard, log_index.messages().count());
+            } else if stats_screen_visible {
+                let fingerprint_stats = stats::fingerprint_stats(log_index.messages());
+                stats_selected = stats_selected.min(fingerprint_stats.len().saturating_sub(1));
+                ui::render_stats_screen(f, f.size(), &fingerprint_stats, stats_selected);
             } else {
                 let chunks = Layout::default()
                     .direction(Direction::Vertical)
@@ -358,13 +998,23 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
 
                 // Save the height for paging (use the list area height)
                 last_list_height = chunks[2].height as usize;
+                last_sql_width = (chunks[0].width.saturating_sub(2) as usize).saturating_sub(4);
 
                 // Render filter input
-                let filter_input = Paragraph::new(filter_text.clone())
+                let mut filter_spans = vec![Span::raw(filter_text.clone())];
+                if let Some(error) = &filter_error {
+                    filter_spans.push(Span::styled(
+                        format!("   ⚠ {}", error),
+                        Style::default().fg(Color::Red),
+                    ));
+                }
+                let filter_input = Paragraph::new(Line::from(filter_spans))
                     .block(
                         Block::default()
                             .borders(Borders::ALL)
-                            .border_style(if filter_focused {
+                            .border_style(if filter_error.is_some() {
+                                Style::default().fg(Color::Red)
+                            } else if filter_focused {
                                 Style::default().fg(Color::Yellow)
                             } else {
                                 Style::default().fg(Color::Gray)
@@ -376,18 +1026,6 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
 
                 f.render_widget(filter_input, chunks[0]);
 
-                // Calculate indicator state
-                let _filtered_lines = filter_log_lines(&log_lines, &filter_text);
-                let _visible_height = last_list_height.saturating_sub(2); // minus border/padding
-                let above_count = main_scroll_offset;
-                let indicator = if above_count > 0 {
-                    Paragraph::new(format!("↑ {above_count} more items above"))
-                        .style(Style::default().fg(Color::Yellow))
-                } else {
-                    Paragraph::new("↓ All items visible").style(Style::default().fg(Color::Green))
-                };
-                f.render_widget(indicator, chunks[1]);
-
                 // Create inner padding area inside the border
                 let inner_area = ratatui::layout::Rect {
                     x: chunks[2].x + 1, // Reduced horizontal padding inside border
@@ -396,38 +1034,109 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
                     height: chunks[2].height.saturating_sub(1), // Reduce height for padding
                 };
 
-                // Create items for the accordion list with top padding
-                let mut items: Vec<ListItem> = vec![
-                    // Add empty line for top padding inside the border
-                    ListItem::new(vec![Line::from("")]),
-                ];
-
                 // Create grouped messages from the log lines
-                let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                
+                let grouped_messages = GroupedLogMessages::from_index(
+                    &log_index,
+                    &mut group_cache,
+                    &pinned_groups,
+                    pins_revision,
+                    &filter,
+                    &filter_text,
+                    group_sort,
+                );
+                let flat_items =
+                    create_flat_navigation_structure(&grouped_messages, &expanded_groups);
+
                 // Calculate dynamic max expanded height based on available screen space
                 // Reserve space for at least one more log entry (minimum 5 lines for context)
                 let min_reserved_space = 5; // Space for next log entry + separators
                 let available_height = last_list_height.saturating_sub(4); // Account for borders/padding
-                let dynamic_max_expanded_height = available_height.saturating_sub(min_reserved_space).max(10); // Minimum 10 lines for expanded content
-                
-                // Render grouped accordions
-                let accordion_items = ui::render_grouped_accordions(
-                    &grouped_messages,
-                    &expanded_groups,
-                    &expanded_uids,
-                    copy_flash_state,
-                    &list_state,
-                    scroll_mode,
-                    &scroll_offsets,
-                    &scroll_cursors,
-                    dynamic_max_expanded_height,
-                    chunks[0].width.saturating_sub(2) as usize,
-                    &filter_text,
-                    &pinned_groups,
+                let dynamic_max_expanded_height =
+                    available_height.saturating_sub(min_reserved_space).max(10); // Minimum 10 lines for expanded content
+                let width = chunks[0].width.saturating_sub(2) as usize;
+
+                // Only render the rows that fit on screen; item 0 is the top padding line
+                let (first_visible, items) = ui::render_viewport(
+                    flat_items.len() + 1,
+                    list_state.selected().unwrap_or(0),
+                    main_scroll_offset,
+                    inner_area.height.saturating_sub(2) as usize, // minus list borders
+                    |index| {
+                        if index == 0 {
+                            return ListItem::new(vec![Line::from("")]);
+                        }
+                        ui::render_accordion_row(
+                            index - 1,
+                            &flat_items[index - 1],
+                            &grouped_messages,
+                            &expanded_groups,
+                            &expanded_ids,
+                            &bookmarked_ids,
+                            copy_flash_state,
+                            &list_state,
+                            scroll_mode,
+                            &scroll_offsets,
+                            &scroll_cursors,
+                            dynamic_max_expanded_height,
+                            width,
+                            &pinned_groups,
+                            &mut render_cache,
+                        )
+                    },
                 );
+                main_scroll_offset = first_visible;
+                let below_count =
+                    (flat_items.len() + 1).saturating_sub(first_visible + items.len());
 
-                items.extend(accordion_items);
+                // Calculate indicator state
+                let above_count = first_visible.saturating_sub(1); // The padding line is not an item
+                let mut indicator_spans = vec![if above_count > 0 {
+                    Span::styled(
+                        format!("↑ {above_count} more items above"),
+                        Style::default().fg(Color::Yellow),
+                    )
+                } else if below_count > 0 {
+                    Span::styled(
+                        format!("↓ {below_count} more items below"),
+                        Style::default().fg(Color::Green),
+                    )
+                } else {
+                    Span::styled("↓ All items visible", Style::default().fg(Color::Green))
+                }];
+                indicator_spans.push(Span::styled(
+                    format!(
+                        "   grouping: {}   sort: {}",
+                        log_index.mode().label(),
+                        group_sort.label()
+                    ),
+                    Style::default().fg(Color::Rgb(150, 150, 150)),
+                ));
+                if render_cache.inline_parameters() {
+                    indicator_spans.push(Span::styled(
+                        "   parameters inlined",
+                        Style::default().fg(Color::Rgb(150, 190, 255)),
+                    ));
+                }
+                if log_index.evicted() > 0 {
+                    indicator_spans.push(Span::styled(
+                        format!("   {} evicted by retention", log_index.evicted()),
+                        Style::default().fg(Color::Rgb(150, 150, 150)),
+                    ));
+                    // Pins and bookmarks are not bounded by the limits, so say how much they hold
+                    if log_index.exempt() > 0 {
+                        indicator_spans.push(Span::styled(
+                            format!(", ⚠ {} kept by pins and bookmarks", log_index.exempt()),
+                            Style::default().fg(Color::Yellow),
+                        ));
+                    }
+                }
+                if rejected_lines.total > 0 {
+                    indicator_spans.push(Span::styled(
+                        format!("   ⚠ {} rejected lines (r to view)", rejected_lines.total),
+                        Style::default().fg(Color::Red),
+                    ));
+                }
+                f.render_widget(Paragraph::new(Line::from(indicator_spans)), chunks[1]);
 
                 let log_list = List::new(items)
                     .block(
@@ -440,571 +1149,816 @@ fn run_tui(rx: mpsc::Receiver<String>) -> anyhow::Result<()> {
                     .highlight_style(Style::default())
                     .highlight_symbol("► ");
 
-                f.render_stateful_widget(log_list, inner_area, &mut list_state);
+                // The list only holds the visible window, so select relative to its first row
+                let mut visible_state = ListState::default().with_selected(
+                    list_state
+                        .selected()
+                        .map(|selected| selected.saturating_sub(first_visible)),
+                );
+                f.render_stateful_widget(log_list, inner_area, &mut visible_state);
             }
         })?;
 
         // Handle keyboard events
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key_event) = event::read()? {
-                use crossterm::event::KeyEventKind;
-                // Only process key press events, not releases or repeats
-                if key_event.kind == KeyEventKind::Press {
-                    let key = key_event;
-                    if help_screen_visible {
-                        // Handle help screen keys
-                        match key.code {
-                            KeyCode::Char('q') => break,
-                            KeyCode::Esc => {
-                                help_screen_visible = false;
-                            }
-                            _ => {}
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key_event) = event::read()?
+        {
+            use crossterm::event::KeyEventKind;
+            // Only process key press events, not releases or repeats
+            if key_event.kind == KeyEventKind::Press {
+                let key = key_event;
+                if help_screen_visible {
+                    // Handle help screen keys
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Esc => {
+                            help_screen_visible = false;
                         }
-                    } else if scroll_mode {
-                        // Handle scroll mode keys
-                        match key.code {
-                            KeyCode::Char('q') => break,
-                            KeyCode::Char('h') => {
-                                scroll_mode = false;
-                            }
-                            KeyCode::Esc => {
-                                // Exit scrollmode and collapse open accordion
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1;
-                                        let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                        let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-                                        
-                                        if actual_index < flat_items.len() {
-                                            if let FlatNavigationItem::Message(message) = &flat_items[actual_index] {
-                                                if let Some(uid) = &message.uid {
-                                                    expanded_uids.remove(uid);
-                                                }
-                                            }
-                                        }
-                                    }
+                        _ => {}
+                    }
+                } else if rejected_panel_visible {
+                    // Handle rejected lines panel keys
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Esc | KeyCode::Char('r') => {
+                            rejected_panel_visible = false;
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            rejected_panel_scroll = rejected_panel_scroll.saturating_add(1);
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            rejected_panel_scroll = rejected_panel_scroll.saturating_sub(1);
+                        }
+                        KeyCode::Char('c') => {
+                            rejected_lines = RejectedLines::default();
+                            rejected_panel_scroll = 0;
+                        }
+                        _ => {}
+                    }
+                } else if timeline_group.is_some() {
+                    // Handle timeline screen keys
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Esc | KeyCode::Char('w') => {
+                            timeline_group = None;
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            timeline_selected += 1; // Clamped to the query count when drawing
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            timeline_selected = timeline_selected.saturating_sub(1);
+                        }
+                        _ => {}
+                    }
+                } else if dashboard_visible {
+                    // Handle dashboard keys
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Esc | KeyCode::Char('D') => {
+                            dashboard_visible = false;
+                        }
+                        _ => {}
+                    }
+                } else if stats_screen_visible {
+                    // Handle statistics screen keys
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Esc | KeyCode::Char('s') => {
+                            stats_screen_visible = false;
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            stats_selected += 1; // Clamped to the row count when drawing
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            stats_selected = stats_selected.saturating_sub(1);
+                        }
+                        _ => {}
+                    }
+                } else if scroll_mode {
+                    // Handle scroll mode keys
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Char('h') => {
+                            scroll_mode = false;
+                        }
+                        KeyCode::Esc => {
+                            // Exit scrollmode and collapse open accordion
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
+                            {
+                                let actual_index = selected - 1;
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                if actual_index < flat_items.len()
+                                    && let FlatNavigationItem::Message(message) =
+                                        &flat_items[actual_index]
+                                {
+                                    expanded_ids.remove(&message.id);
                                 }
-                                scroll_mode = false;
                             }
-                            KeyCode::Char('j') | KeyCode::Down => {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1;
-                                        let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                        let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-                                        
-                                        if actual_index < flat_items.len() {
-                                            if let FlatNavigationItem::Message(message) = &flat_items[actual_index] {
-                                                // Calculate actual content lines for this message
-                                                let mut total_lines = 0;
-
-                                                if message.statement.contains("[-- Batch Command") {
-                                                    // Count batch processing lines
-                                                    let mut current_batch_sql = String::new();
-                                                    for statement_line in message.statement.lines() {
-                                                        if statement_line.starts_with("[-- Batch Command") {
-                                                            if !current_batch_sql.trim().is_empty() {
-                                                                total_lines += 1; // batch header
-                                                                let format_options = FormatOptions {
-                                                                    indent: sqlformat::Indent::Spaces(2),
-                                                                    uppercase: Some(false),
-                                                                    lines_between_queries: 1,
-                                                                    ignore_case_convert: Some(vec![]),
-                                                                };
-                                                                let formatted_sql = format(
-                                                                    &current_batch_sql.trim(),
-                                                                    &QueryParams::None,
-                                                                    &format_options,
-                                                                );
-                                                                if formatted_sql.trim().is_empty() {
-                                                                    total_lines += current_batch_sql.lines().count().max(1);
-                                                                } else {
-                                                                    total_lines += formatted_sql.lines().count();
-                                                                }
-                                                                total_lines += 1; // separator
-                                                            }
-                                                            current_batch_sql.clear();
-                                                        } else {
-                                                            if !current_batch_sql.is_empty() {
-                                                                current_batch_sql.push('\n');
-                                                            }
-                                                            current_batch_sql.push_str(statement_line);
-                                                        }
-                                                    }
-                                                    // Final batch
-                                                    if !current_batch_sql.trim().is_empty() {
-                                                        total_lines += 1; // batch header
-                                                        let format_options = FormatOptions {
-                                                            indent: sqlformat::Indent::Spaces(2),
-                                                            uppercase: Some(false),
-                                                            lines_between_queries: 1,
-                                                            ignore_case_convert: Some(vec![]),
-                                                        };
-                                                        let formatted_sql = format(
-                                                            &current_batch_sql.trim(),
-                                                            &QueryParams::None,
-                                                            &format_options,
-                                                        );
-                                                        if formatted_sql.trim().is_empty() {
-                                                            total_lines += current_batch_sql.lines().count().max(1);
-                                                        } else {
-                                                            total_lines += formatted_sql.lines().count();
-                                                        }
-                                                    }
-                                                } else {
-                                                    // Regular statement
-                                                    let format_options = FormatOptions {
-                                                        indent: sqlformat::Indent::Spaces(2),
-                                                        uppercase: Some(false),
-                                                        lines_between_queries: 1,
-                                                        ignore_case_convert: Some(vec![]),
-                                                    };
-                                                    let formatted_sql = format(&message.statement, &QueryParams::None, &format_options);
-                                                    if formatted_sql.trim().is_empty() {
-                                                        total_lines += message.statement.lines().count().max(1);
-                                                    } else {
-                                                        total_lines += formatted_sql.lines().count();
-                                                    }
-                                                    total_lines += 1; // end statement marker
-                                                }
-
-                                                let current_cursor = scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
-                                                let current_offset = scroll_offsets.get(&actual_index).cloned().unwrap_or(0);
-
-                                                // Move cursor down if not at the end
-                                                if current_cursor < total_lines.saturating_sub(1) {
-                                                    let new_cursor = current_cursor + 1;
-                                                    scroll_cursors.insert(actual_index, new_cursor);
-
-                                                    // Calculate dynamic expanded height
-                                                    let min_reserved_space = 5;
-                                                    let available_height = last_list_height.saturating_sub(4);
-                                                    let dynamic_max_expanded_height = available_height.saturating_sub(min_reserved_space).max(10);
-
-                                                    // Auto-scroll if cursor goes beyond visible area
-                                                    if new_cursor >= current_offset + dynamic_max_expanded_height {
-                                                        scroll_offsets.insert(actual_index, current_offset + 1);
-                                                    }
-                                                }
-                                            }
+                            scroll_mode = false;
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
+                            {
+                                let actual_index = selected - 1;
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                if actual_index < flat_items.len()
+                                    && let FlatNavigationItem::Message(message) =
+                                        &flat_items[actual_index]
+                                {
+                                    // Content lines for this message, as rendered
+                                    let total_lines =
+                                        render_cache.statement_lines(message, last_sql_width).len();
+
+                                    let current_cursor =
+                                        scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
+                                    let current_offset =
+                                        scroll_offsets.get(&actual_index).cloned().unwrap_or(0);
+
+                                    // Move cursor down if not at the end
+                                    if current_cursor < total_lines.saturating_sub(1) {
+                                        let new_cursor = current_cursor + 1;
+                                        scroll_cursors.insert(actual_index, new_cursor);
+
+                                        // Calculate dynamic expanded height
+                                        let min_reserved_space = 5;
+                                        let available_height = last_list_height.saturating_sub(4);
+                                        let dynamic_max_expanded_height = available_height
+                                            .saturating_sub(min_reserved_space)
+                                            .max(10);
+
+                                        // Auto-scroll if cursor goes beyond visible area
+                                        if new_cursor
+                                            >= current_offset + dynamic_max_expanded_height
+                                        {
+                                            scroll_offsets.insert(actual_index, current_offset + 1);
                                         }
                                     }
                                 }
                             }
-                            KeyCode::Char('k') | KeyCode::Up => {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1;
-                                        let current_cursor =
-                                            scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
-                                        let current_offset =
-                                            scroll_offsets.get(&actual_index).cloned().unwrap_or(0);
-
-                                        // Move cursor up if not at the top
-                                        if current_cursor > 0 {
-                                            let new_cursor = current_cursor - 1;
-                                            scroll_cursors.insert(actual_index, new_cursor);
-
-                                            // Auto-scroll if cursor goes above visible area
-                                            if new_cursor < current_offset {
-                                                scroll_offsets
-                                                    .insert(actual_index, current_offset - 1);
-                                            }
-                                        }
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
+                            {
+                                let actual_index = selected - 1;
+                                let current_cursor =
+                                    scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
+                                let current_offset =
+                                    scroll_offsets.get(&actual_index).cloned().unwrap_or(0);
+
+                                // Move cursor up if not at the top
+                                if current_cursor > 0 {
+                                    let new_cursor = current_cursor - 1;
+                                    scroll_cursors.insert(actual_index, new_cursor);
+
+                                    // Auto-scroll if cursor goes above visible area
+                                    if new_cursor < current_offset {
+                                        scroll_offsets.insert(actual_index, current_offset - 1);
                                     }
                                 }
                             }
-                            KeyCode::Char('d')
-                                if key.modifiers == crossterm::event::KeyModifiers::CONTROL =>
+                        }
+                        KeyCode::Char('d')
+                            if key.modifiers == crossterm::event::KeyModifiers::CONTROL =>
+                        {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
                             {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1;
-                                                                let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                        let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-                                        
-                                        if actual_index < flat_items.len() {
-                                            if let FlatNavigationItem::Message(message) = &flat_items[actual_index] {
-                                                // Page down (Ctrl+d) - move cursor down by half a page
-                                                let current_cursor =
-                                                    scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
-                                                let current_offset =
-                                                    scroll_offsets.get(&actual_index).cloned().unwrap_or(0);
-                                                // Calculate dynamic expanded height
-                                                let min_reserved_space = 5;
-                                                let available_height = last_list_height.saturating_sub(4);
-                                                let dynamic_max_expanded_height = available_height.saturating_sub(min_reserved_space).max(10);
-                                                let page_size = dynamic_max_expanded_height / 2; // Half page like vim
-
-                                                // Calculate total lines for this message
-                                                let mut total_lines = 0;
-
-                                                if message.statement.contains("[-- Batch Command") {
-                                                    // Count batch processing lines
-                                                    let mut current_batch_sql = String::new();
-                                                    for statement_line in message.statement.lines() {
-                                                        if statement_line.starts_with("[-- Batch Command") {
-                                                            if !current_batch_sql.trim().is_empty() {
-                                                                total_lines += 1; // batch header
-                                                                let format_options = FormatOptions {
-                                                                    indent: sqlformat::Indent::Spaces(2),
-                                                                    uppercase: Some(false),
-                                                                    lines_between_queries: 1,
-                                                                    ignore_case_convert: Some(vec![]),
-                                                                };
-                                                                let formatted_sql = format(
-                                                                    &current_batch_sql.trim(),
-                                                                    &QueryParams::None,
-                                                                    &format_options,
-                                                                );
-                                                                if formatted_sql.trim().is_empty() {
-                                                                    total_lines += current_batch_sql.lines().count().max(1);
-                                                                } else {
-                                                                    total_lines += formatted_sql.lines().count();
-                                                                }
-                                                                total_lines += 1; // separator
-                                                            }
-                                                            current_batch_sql.clear();
-                                                        } else {
-                                                            if !current_batch_sql.is_empty() {
-                                                                current_batch_sql.push('\n');
-                                                            }
-                                                            current_batch_sql.push_str(statement_line);
-                                                        }
-                                                    }
-                                                    // Final batch
-                                                    if !current_batch_sql.trim().is_empty() {
-                                                        total_lines += 1; // batch header
-                                                        let format_options = FormatOptions {
-                                                            indent: sqlformat::Indent::Spaces(2),
-                                                            uppercase: Some(false),
-                                                            lines_between_queries: 1,
-                                                            ignore_case_convert: Some(vec![]),
-                                                        };
-                                                        let formatted_sql = format(
-                                                            &current_batch_sql.trim(),
-                                                            &QueryParams::None,
-                                                            &format_options,
-                                                        );
-                                                        if formatted_sql.trim().is_empty() {
-                                                            total_lines += current_batch_sql.lines().count().max(1);
-                                                        } else {
-                                                            total_lines += formatted_sql.lines().count();
-                                                        }
-                                                    }
-                                                } else {
-                                                    // Regular statement
-                                                    let format_options = FormatOptions {
-                                                        indent: sqlformat::Indent::Spaces(2),
-                                                        uppercase: Some(false),
-                                                        lines_between_queries: 1,
-                                                        ignore_case_convert: Some(vec![]),
-                                                    };
-                                                    let formatted_sql = format(&message.statement, &QueryParams::None, &format_options);
-                                                    if formatted_sql.trim().is_empty() {
-                                                        total_lines += message.statement.lines().count().max(1);
-                                                    } else {
-                                                        total_lines += formatted_sql.lines().count();
-                                                    }
-                                                    total_lines += 1; // end statement marker
-                                                }
-
-                                                // Move cursor down by half page
-                                                let new_cursor = std::cmp::min(
-                                                    current_cursor + page_size,
-                                                    total_lines.saturating_sub(1),
-                                                );
-                                                scroll_cursors.insert(actual_index, new_cursor);
-
-                                                // Auto-scroll if cursor goes beyond visible area
-                                                if new_cursor >= current_offset + dynamic_max_expanded_height {
-                                                    let new_offset = std::cmp::min(
-                                                        current_offset + page_size,
-                                                        total_lines.saturating_sub(dynamic_max_expanded_height),
-                                                    );
-                                                    scroll_offsets.insert(actual_index, new_offset);
-                                                }
-                                            }
-                                        }
+                                let actual_index = selected - 1;
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                if actual_index < flat_items.len()
+                                    && let FlatNavigationItem::Message(message) =
+                                        &flat_items[actual_index]
+                                {
+                                    // Page down (Ctrl+d) - move cursor down by half a page
+                                    let current_cursor =
+                                        scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
+                                    let current_offset =
+                                        scroll_offsets.get(&actual_index).cloned().unwrap_or(0);
+                                    // Calculate dynamic expanded height
+                                    let min_reserved_space = 5;
+                                    let available_height = last_list_height.saturating_sub(4);
+                                    let dynamic_max_expanded_height =
+                                        available_height.saturating_sub(min_reserved_space).max(10);
+                                    let page_size = dynamic_max_expanded_height / 2; // Half page like vim
+
+                                    // Content lines for this message, as rendered
+                                    let total_lines =
+                                        render_cache.statement_lines(message, last_sql_width).len();
+
+                                    // Move cursor down by half page
+                                    let new_cursor = std::cmp::min(
+                                        current_cursor + page_size,
+                                        total_lines.saturating_sub(1),
+                                    );
+                                    scroll_cursors.insert(actual_index, new_cursor);
+
+                                    // Auto-scroll if cursor goes beyond visible area
+                                    if new_cursor >= current_offset + dynamic_max_expanded_height {
+                                        let new_offset = std::cmp::min(
+                                            current_offset + page_size,
+                                            total_lines.saturating_sub(dynamic_max_expanded_height),
+                                        );
+                                        scroll_offsets.insert(actual_index, new_offset);
                                     }
                                 }
                             }
-                            KeyCode::Char('u')
-                                if key.modifiers == crossterm::event::KeyModifiers::CONTROL =>
+                        }
+                        KeyCode::Char('u')
+                            if key.modifiers == crossterm::event::KeyModifiers::CONTROL =>
+                        {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
                             {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1;
-                                        // Page up (Ctrl+u) - move cursor up by half a page
-                                        let current_cursor =
-                                            scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
-                                        let current_offset =
-                                            scroll_offsets.get(&actual_index).cloned().unwrap_or(0);
-                                        // Calculate dynamic expanded height
-                                        let min_reserved_space = 5;
-                                        let available_height = last_list_height.saturating_sub(4);
-                                        let dynamic_max_expanded_height = available_height.saturating_sub(min_reserved_space).max(10);
-                                        let page_size = dynamic_max_expanded_height / 2; // Half page like vim
-
-                                        // Move cursor up by half page
-                                        let new_cursor = current_cursor.saturating_sub(page_size);
-                                        scroll_cursors.insert(actual_index, new_cursor);
-
-                                        // Auto-scroll if cursor goes above visible area
-                                        if new_cursor < current_offset {
-                                            let new_offset =
-                                                current_offset.saturating_sub(page_size);
-                                            scroll_offsets.insert(actual_index, new_offset);
-                                        }
-                                    }
+                                let actual_index = selected - 1;
+                                // Page up (Ctrl+u) - move cursor up by half a page
+                                let current_cursor =
+                                    scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
+                                let current_offset =
+                                    scroll_offsets.get(&actual_index).cloned().unwrap_or(0);
+                                // Calculate dynamic expanded height
+                                let min_reserved_space = 5;
+                                let available_height = last_list_height.saturating_sub(4);
+                                let dynamic_max_expanded_height =
+                                    available_height.saturating_sub(min_reserved_space).max(10);
+                                let page_size = dynamic_max_expanded_height / 2; // Half page like vim
+
+                                // Move cursor up by half page
+                                let new_cursor = current_cursor.saturating_sub(page_size);
+                                scroll_cursors.insert(actual_index, new_cursor);
+
+                                // Auto-scroll if cursor goes above visible area
+                                if new_cursor < current_offset {
+                                    let new_offset = current_offset.saturating_sub(page_size);
+                                    scroll_offsets.insert(actual_index, new_offset);
                                 }
                             }
-                            KeyCode::Char('y') => {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1;
-                                        let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                        let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-                                        
-                                        if actual_index < flat_items.len() {
-                                            if let FlatNavigationItem::Message(message) = &flat_items[actual_index] {
-                                                let cursor_pos = scroll_cursors
-                                                    .get(&actual_index)
-                                                    .cloned()
-                                                    .unwrap_or(0);
-
-                                                let text_to_copy =
-                                                    if message.statement.contains("[-- Batch Command") {
-                                                        format::extract_batch_statement_at_cursor(
-                                                            &message.statement,
-                                                            cursor_pos,
-                                                        )
-                                                    } else {
-                                                        let format_options = FormatOptions {
-                                                            indent: sqlformat::Indent::Spaces(2),
-                                                            uppercase: Some(false),
-                                                            lines_between_queries: 1,
-                                                            ignore_case_convert: Some(vec![]),
-                                                        };
-                                                        let formatted_sql = format(
-                                                            &message.statement,
-                                                            &QueryParams::None,
-                                                            &format_options,
-                                                        );
-                                                        if formatted_sql.trim().is_empty() {
-                                                            message.statement.clone()
-                                                        } else {
-                                                            formatted_sql
-                                                        }
-                                                    };
-
-                                                if let Some(ref mut cb) = clipboard {
-                                                    if cb.set_text(text_to_copy).is_ok() {
-                                                        // Flash the indicator on the correct item (use actual_index for rendering)
-                                                        copy_flash_state = Some((
-                                                            actual_index,
-                                                            std::time::Instant::now(),
-                                                        ));
-                                                    }
-                                                }
-                                            }
+                        }
+                        KeyCode::Char('y') => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
+                            {
+                                let actual_index = selected - 1;
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                if actual_index < flat_items.len()
+                                    && let FlatNavigationItem::Message(message) =
+                                        &flat_items[actual_index]
+                                {
+                                    let cursor_pos =
+                                        scroll_cursors.get(&actual_index).cloned().unwrap_or(0);
+
+                                    // Copy what is shown, so inlined parameters are included
+                                    let statement = params::statement_text(
+                                        message,
+                                        render_cache.inline_parameters(),
+                                    );
+                                    let text_to_copy = if statement.contains("[-- Batch Command") {
+                                        format::extract_batch_statement_at_cursor(
+                                            &statement, cursor_pos,
+                                        )
+                                    } else {
+                                        let format_options = format::format_options();
+                                        let formatted_sql =
+                                            format(&statement, &QueryParams::None, &format_options);
+                                        if formatted_sql.trim().is_empty() {
+                                            statement.into_owned()
+                                        } else {
+                                            formatted_sql
                                         }
+                                    };
+
+                                    if let Some(ref mut cb) = clipboard
+                                        && cb.set_text(text_to_copy).is_ok()
+                                    {
+                                        // Flash the indicator on the correct item (use actual_index for rendering)
+                                        copy_flash_state =
+                                            Some((actual_index, std::time::Instant::now()));
                                     }
                                 }
                             }
-                            _ => {}
                         }
-                    } else if filter_focused {
-                        // Handle filter input
-                        match key.code {
-                            KeyCode::Char('q') => break,
-                            KeyCode::Esc => {
-                                filter_focused = false;
-                            }
-                            KeyCode::Char(c) => {
-                                filter_text.push(c);
-                            }
-                            KeyCode::Backspace => {
-                                filter_text.pop();
-                            }
-                            KeyCode::Enter => {
-                                filter_focused = false;
+                        _ => {}
+                    }
+                } else if filter_focused {
+                    // Handle filter input
+                    match key.code {
+                        KeyCode::Esc => {
+                            filter_focused = false;
+                        }
+                        KeyCode::Char(c) => {
+                            filter_text.push(c);
+                            update_filter(&filter_text, &mut filter, &mut filter_error);
+                        }
+                        KeyCode::Backspace => {
+                            filter_text.pop();
+                            update_filter(&filter_text, &mut filter, &mut filter_error);
+                        }
+                        KeyCode::Enter => {
+                            filter_focused = false;
+                        }
+                        _ => {}
+                    }
+                } else {
+                    // Handle normal accordion navigation
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Char('f') => {
+                            filter_focused = true;
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 1
+                            {
+                                // Skip the padding line at index 0
+                                list_state.select(Some(selected - 1));
                             }
-                            _ => {}
                         }
-                    } else {
-                        // Handle normal accordion navigation
-                        match key.code {
-                            KeyCode::Char('q') => break,
-                            KeyCode::Char('f') => {
-                                filter_focused = true;
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            let grouped_messages = GroupedLogMessages::from_index(
+                                &log_index,
+                                &mut group_cache,
+                                &pinned_groups,
+                                pins_revision,
+                                &filter,
+                                &filter_text,
+                                group_sort,
+                            );
+                            let total_items =
+                                count_total_rendered_items(&grouped_messages, &expanded_groups);
+                            if let Some(selected) = list_state.selected() {
+                                if selected < total_items {
+                                    // Account for padding line
+                                    list_state.select(Some(selected + 1));
+                                }
+                            } else if total_items > 0 {
+                                list_state.select(Some(1)); // Start at index 1 (first actual item)
                             }
-                            KeyCode::Up | KeyCode::Char('k') => {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 1 {
-                                        // Skip the padding line at index 0
-                                        list_state.select(Some(selected - 1));
-                                    }
+                        }
+                        KeyCode::Char('d')
+                            if key.modifiers == crossterm::event::KeyModifiers::CONTROL =>
+                        {
+                            // Page down (Ctrl+d) - move selection down by half a page
+                            if let Some(selected) = list_state.selected() {
+                                let page_size = last_list_height.saturating_sub(2) / 2; // half page, minus padding
+                                // Last selectable row, after the padding line
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let max_index =
+                                    count_total_rendered_items(&grouped_messages, &expanded_groups);
+                                let new_selected = std::cmp::min(selected + page_size, max_index);
+                                if new_selected > 0 && new_selected <= max_index {
+                                    list_state.select(Some(new_selected));
                                 }
                             }
-                            KeyCode::Down | KeyCode::Char('j') => {
-                                let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                let total_items = count_total_rendered_items(&grouped_messages, &expanded_groups, &filter_text);
-                                if let Some(selected) = list_state.selected() {
-                                    if selected < total_items {
-                                        // Account for padding line
-                                        list_state.select(Some(selected + 1));
-                                    }
-                                } else if total_items > 0 {
-                                    list_state.select(Some(1)); // Start at index 1 (first actual item)
+                        }
+                        KeyCode::Char('u')
+                            if key.modifiers == crossterm::event::KeyModifiers::CONTROL =>
+                        {
+                            // Page up (Ctrl+u) - move selection up by half a page
+                            if let Some(selected) = list_state.selected() {
+                                let page_size = last_list_height.saturating_sub(2) / 2; // half page, minus padding
+                                let new_selected = selected.saturating_sub(page_size);
+                                if new_selected > 0 {
+                                    list_state.select(Some(new_selected));
+                                } else {
+                                    list_state.select(Some(1)); // Don't go above first item
                                 }
                             }
-                            KeyCode::Char('d')
-                                if key.modifiers == crossterm::event::KeyModifiers::CONTROL =>
+                        }
+                        KeyCode::Enter => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
                             {
-                                // Page down (Ctrl+d) - move selection down by half a page
-                                if let Some(selected) = list_state.selected() {
-                                    let page_size =
-                                        (last_list_height.saturating_sub(2) / 2) as usize; // half page, minus padding
-                                    let max_index = log_lines.len();
-                                    let new_selected =
-                                        std::cmp::min(selected + page_size, max_index);
-                                    if new_selected > 0 && new_selected <= max_index {
-                                        list_state.select(Some(new_selected));
+                                let actual_index = selected - 1; // Convert to actual navigation index
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                if actual_index < flat_items.len() {
+                                    match &flat_items[actual_index] {
+                                        FlatNavigationItem::GroupHeader(group, _) => {
+                                            // Toggle group expansion
+                                            if expanded_groups.contains(group) {
+                                                expanded_groups.remove(group);
+                                            } else {
+                                                expanded_groups.insert(group.clone());
+                                            }
+                                        }
+                                        FlatNavigationItem::Message(message) => {
+                                            // Toggle individual message expansion
+                                            if !expanded_ids.remove(&message.id) {
+                                                expanded_ids.insert(message.id);
+                                            }
+                                        }
                                     }
                                 }
                             }
-                            KeyCode::Char('u')
-                                if key.modifiers == crossterm::event::KeyModifiers::CONTROL =>
+                        }
+                        KeyCode::Char('l') => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
                             {
-                                // Page up (Ctrl+u) - move selection up by half a page
-                                if let Some(selected) = list_state.selected() {
-                                    let page_size =
-                                        (last_list_height.saturating_sub(2) / 2) as usize; // half page, minus padding
-                                    let new_selected = selected.saturating_sub(page_size);
-                                    if new_selected > 0 {
-                                        list_state.select(Some(new_selected));
-                                    } else {
-                                        list_state.select(Some(1)); // Don't go above first item
-                                    }
+                                let actual_index = selected - 1;
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                if actual_index < flat_items.len()
+                                    && let FlatNavigationItem::Message(message) =
+                                        &flat_items[actual_index]
+                                    && expanded_ids.contains(&message.id)
+                                {
+                                    scroll_mode = true;
+                                    // Always reset scroll position when entering scroll mode
+                                    scroll_offsets.insert(actual_index, 0);
+                                    scroll_cursors.insert(actual_index, 0);
                                 }
                             }
-                            KeyCode::Enter => {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1; // Convert to actual navigation index
-                                        let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                        let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-                                        
-                                        if actual_index < flat_items.len() {
-                                            match &flat_items[actual_index] {
-                                                FlatNavigationItem::GroupHeader(group) => {
-                                                    // Toggle group expansion
-                                                    if expanded_groups.contains(group) {
-                                                        expanded_groups.remove(group);
-                                                    } else {
-                                                        expanded_groups.insert(group.clone());
-                                                    }
-                                                }
-                                                FlatNavigationItem::Message(message) => {
-                                                    // Toggle individual message expansion
-                                                    if let Some(uid) = &message.uid {
-                                                        if expanded_uids.contains(uid) {
-                                                            expanded_uids.remove(uid);
-                                                        } else {
-                                                            expanded_uids.insert(uid.clone());
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
+                        }
+                        KeyCode::Char('h') => {
+                            help_screen_visible = true;
+                        }
+                        KeyCode::Char('r') => {
+                            rejected_panel_visible = true;
+                            rejected_panel_scroll = 0;
+                        }
+                        KeyCode::Char('g') => {
+                            // Park this mode's state, including the query under the cursor
+                            let current_id = list_state.selected().and_then(|selected| {
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+                                selected_message_id(&flat_items, selected)
+                            });
+                            let mode = log_index.mode();
+                            parked_modes.insert(
+                                mode,
+                                ParkedMode {
+                                    expanded_groups: std::mem::take(&mut expanded_groups),
+                                    pinned_groups: std::mem::take(&mut pinned_groups),
+                                    selected: list_state.selected(),
+                                    selected_id: current_id,
+                                    scroll_offset: main_scroll_offset,
+                                },
+                            );
+
+                            // ...and pick up the next one where it was left
+                            log_index.regroup(mode.next());
+                            let parked = parked_modes.remove(&mode.next()).unwrap_or_default();
+                            expanded_groups = parked.expanded_groups;
+                            pinned_groups = parked.pinned_groups;
+                            pins_revision += 1;
+                            selected_id = parked.selected_id;
+                            main_scroll_offset = parked.scroll_offset;
+                            list_state.select(parked.selected.or(Some(1)));
+                            // Scroll positions are keyed by list index, which differs per mode
+                            scroll_offsets.clear();
+                            scroll_cursors.clear();
+
+                            // Follow the selected query to wherever it is listed now
+                            if let Some(id) = selected_id {
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+                                if let Some(index) = message_position(&flat_items, id) {
+                                    list_state.select(Some(index + 1)); // +1 for padding line
                                 }
                             }
-                            KeyCode::Char('l') => {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1;
-                                        let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                        let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-                                        
-                                        if actual_index < flat_items.len() {
-                                            if let FlatNavigationItem::Message(message) = &flat_items[actual_index] {
-                                                if let Some(uid) = &message.uid {
-                                                    if expanded_uids.contains(uid) {
-                                                        scroll_mode = true;
-                                                        // Always reset scroll position when entering scroll mode
-                                                        scroll_offsets.insert(actual_index, 0);
-                                                        scroll_cursors.insert(actual_index, 0);
-                                                    }
-                                                }
-                                            }
-                                        }
+                        }
+                        KeyCode::Char('o') | KeyCode::Char('O') => {
+                            let grouped_messages = GroupedLogMessages::from_index(
+                                &log_index,
+                                &mut group_cache,
+                                &pinned_groups,
+                                pins_revision,
+                                &filter,
+                                &filter_text,
+                                group_sort,
+                            );
+                            let flat_items = create_flat_navigation_structure(
+                                &grouped_messages,
+                                &expanded_groups,
+                            );
+                            let current_id = list_state
+                                .selected()
+                                .and_then(|selected| selected_message_id(&flat_items, selected));
+
+                            // o moves on to the next sort key, O flips the direction
+                            group_sort = if key.code == KeyCode::Char('o') {
+                                group_sort.next()
+                            } else {
+                                group_sort.reversed()
+                            };
+
+                            // Keep the selected query selected while the list reorders
+                            if let Some(id) = current_id {
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+                                if let Some(index) = message_position(&flat_items, id) {
+                                    list_state.select(Some(index + 1)); // +1 for padding line
+                                }
+                            }
+                        }
+                        KeyCode::Char('D') => {
+                            dashboard_visible = true;
+                        }
+                        KeyCode::Char('s') => {
+                            stats_screen_visible = true;
+                            stats_selected = 0;
+                        }
+                        KeyCode::Char('w') => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
+                            {
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                // Show the selected group, or the group of the selected query
+                                timeline_group = match flat_items.get(selected - 1) {
+                                    Some(FlatNavigationItem::GroupHeader(group, _)) => {
+                                        Some(group.clone())
                                     }
+                                    Some(FlatNavigationItem::Message(message)) => {
+                                        Some(RequestGroup::from_message(message, log_index.mode()))
+                                    }
+                                    None => None,
+                                };
+                                timeline_selected = 0;
+                            }
+                        }
+                        KeyCode::Char('b') => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
+                            {
+                                let actual_index = selected - 1;
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                // Toggle the bookmark on the selected query
+                                if actual_index < flat_items.len()
+                                    && let FlatNavigationItem::Message(message) =
+                                        &flat_items[actual_index]
+                                    && !bookmarked_ids.remove(&message.id)
+                                {
+                                    bookmarked_ids.insert(message.id);
                                 }
+                                log_index.exemptions_changed();
                             }
-                            KeyCode::Char('h') => {
-                                help_screen_visible = true;
+                        }
+                        KeyCode::Char('e') => {
+                            let grouped_messages = GroupedLogMessages::from_index(
+                                &log_index,
+                                &mut group_cache,
+                                &pinned_groups,
+                                pins_revision,
+                                &filter,
+                                &filter_text,
+                                group_sort,
+                            );
+                            let flat_items = create_flat_navigation_structure(
+                                &grouped_messages,
+                                &expanded_groups,
+                            );
+                            let current = list_state
+                                .selected()
+                                .and_then(|selected| flat_items.get(selected.checked_sub(1)?));
+
+                            if let Some((group, id)) = grouped_messages.next_error(current) {
+                                // Open the group (and the tree nodes above it) so the failed
+                                // query can be selected
+                                expanded_groups.extend(group.ancestors());
+                                expanded_groups.insert(group);
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+                                if let Some(index) = flat_items.iter().position(|item| {
+                                    matches!(item, FlatNavigationItem::Message(msg) if msg.id == id)
+                                }) {
+                                    list_state.select(Some(index + 1)); // +1 for padding line
+                                }
                             }
-                            KeyCode::Char('t') => {
-                                if let Some(selected) = list_state.selected() {
-                                    if selected > 0 {
-                                        let actual_index = selected - 1;
-                                        let grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                        let flat_items = create_flat_navigation_structure(&grouped_messages, &expanded_groups, &filter_text);
-
-                                        if actual_index < flat_items.len() {
-                                            if let FlatNavigationItem::GroupHeader(group) = &flat_items[actual_index] {
-                                                // Capture the group we're toggling
-                                                let target_group = group.clone();
-
-                                                // Toggle pin status for the selected group
-                                                if pinned_groups.contains(group) {
-                                                    pinned_groups.remove(group);
-                                                } else {
-                                                    pinned_groups.insert(group.clone());
-                                                }
-
-                                                // After toggling, find where this group ended up and restore selection
-                                                let updated_grouped_messages = GroupedLogMessages::from_messages(&log_lines, &pinned_groups);
-                                                let updated_flat_items = create_flat_navigation_structure(&updated_grouped_messages, &expanded_groups, &filter_text);
-
-                                                // Find the new position of the target group
-                                                for (new_index, item) in updated_flat_items.iter().enumerate() {
-                                                    if let FlatNavigationItem::GroupHeader(updated_group) = item {
-                                                        if *updated_group == target_group {
-                                                            list_state.select(Some(new_index + 1)); // +1 for padding line
-                                                            break;
-                                                        }
-                                                    }
-                                                }
-                                            }
+                        }
+                        KeyCode::Char('p') => {
+                            render_cache.toggle_inline_parameters();
+                        }
+                        KeyCode::Char('t') => {
+                            if let Some(selected) = list_state.selected()
+                                && selected > 0
+                            {
+                                let actual_index = selected - 1;
+                                let grouped_messages = GroupedLogMessages::from_index(
+                                    &log_index,
+                                    &mut group_cache,
+                                    &pinned_groups,
+                                    pins_revision,
+                                    &filter,
+                                    &filter_text,
+                                    group_sort,
+                                );
+                                let flat_items = create_flat_navigation_structure(
+                                    &grouped_messages,
+                                    &expanded_groups,
+                                );
+
+                                if actual_index < flat_items.len()
+                                    && let FlatNavigationItem::GroupHeader(group, _) =
+                                        &flat_items[actual_index]
+                                {
+                                    // Capture the group we're toggling
+                                    let target_group = group.clone();
+
+                                    // Toggle pin status for the selected group
+                                    if pinned_groups.contains(group) {
+                                        pinned_groups.remove(group);
+                                    } else {
+                                        pinned_groups.insert(group.clone());
+                                    }
+                                    pins_revision += 1;
+                                    log_index.exemptions_changed();
+
+                                    // After toggling, find where this group ended up and restore selection
+                                    let updated_grouped_messages = GroupedLogMessages::from_index(
+                                        &log_index,
+                                        &mut group_cache,
+                                        &pinned_groups,
+                                        pins_revision,
+                                        &filter,
+                                        &filter_text,
+                                        group_sort,
+                                    );
+                                    let updated_flat_items = create_flat_navigation_structure(
+                                        &updated_grouped_messages,
+                                        &expanded_groups,
+                                    );
+
+                                    // Find the new position of the target group
+                                    for (new_index, item) in updated_flat_items.iter().enumerate() {
+                                        if let FlatNavigationItem::GroupHeader(updated_group, _) =
+                                            item
+                                            && *updated_group == target_group
+                                        {
+                                            list_state.select(Some(new_index + 1)); // +1 for padding line
+                                            break;
                                         }
                                     }
                                 }
                             }
-                            KeyCode::Char('c') => {
-                                // Clear all log entries for a clean slate
-                                log_lines.clear();
-                                expanded_uids.clear();
-                                expanded_groups.clear();
-                                scroll_offsets.clear();
-                                scroll_cursors.clear();
-                                selected_uid = None;
-                                list_state.select(Some(1)); // Reset selection to first position
-                                main_scroll_offset = 0;
-                            }
-                            _ => {}
                         }
+                        KeyCode::Char('c') => {
+                            // Clear all log entries for a clean slate
+                            log_index.clear();
+                            expanded_ids.clear();
+                            expanded_groups.clear();
+                            scroll_offsets.clear();
+                            scroll_cursors.clear();
+                            render_cache.clear();
+                            bookmarked_ids.clear();
+                            selected_id = None;
+                            list_state.select(Some(1)); // Reset selection to first position
+                            main_scroll_offset = 0;
+                        }
+                        _ => {}
                     }
                 }
             }
         }
     }
 
-    // Clean up terminal
-    disable_raw_mode()?;
-    execute!(std::io::stdout(), LeaveAlternateScreen)?;
     Ok(())
 }
 
@@ -1024,16 +1978,17 @@ fn interpolate_color(ms: u64) -> (u8, u8, u8) {
     let green = (38, 255, 0);
     let yellow = (255, 252, 66);
     let red = (237, 83, 83);
+    let thresholds = config::get().thresholds;
 
-    if ms < 250 {
-        let t = ms as f64 / 500.0;
+    if ms < thresholds.warn_ms {
+        let t = ms as f64 / thresholds.warn_ms as f64;
         (
             lerp(green.0, yellow.0, t),
             lerp(green.1, yellow.1, t),
             lerp(green.2, yellow.2, t),
         )
-    } else if ms < 5000 {
-        let t = (ms.saturating_sub(500)) as f64 / (5000.0 - 500.0);
+    } else if ms < thresholds.slow_ms {
+        let t = (ms - thresholds.warn_ms) as f64 / (thresholds.slow_ms - thresholds.warn_ms) as f64;
         (
             lerp(yellow.0, red.0, t),
             lerp(yellow.1, red.1, t),
@@ -1057,125 +2012,262 @@ fn get_http_method_color(method: &str) -> Color {
     }
 }
 
-fn filter_log_lines<'a>(
-    log_lines: &'a [SqlLogMessage],
+// Re-parse the filter text, keeping the previous filter when the new text does not parse
+fn update_filter(
     filter_text: &str,
-) -> Vec<&'a SqlLogMessage> {
-    if filter_text.is_empty() {
-        return log_lines.iter().collect();
+    filter: &mut filter::Filter,
+    filter_error: &mut Option<String>,
+) {
+    match filter::parse_filter(filter_text) {
+        Ok(parsed) => {
+            *filter = parsed;
+            *filter_error = None;
+        }
+        Err(error) => *filter_error = Some(error),
     }
-
-    log_lines
-        .iter()
-        .filter(|line| {
-            // Check http_method or "CALL" when http_method is null
-            let method_match = if line.http_method.is_none() {
-                "CALL".contains(filter_text)
-            } else {
-                line.http_method
-                    .as_ref()
-                    .map_or(false, |method| method.contains(filter_text))
-            };
-
-            // Check endpoint
-            let endpoint_match = line
-                .endpoint
-                .as_ref()
-                .map_or(false, |endpoint| endpoint.contains(filter_text));
-
-            // Check caller_class
-            let caller_class_match = line
-                .caller_class
-                .as_ref()
-                .map_or(false, |class| class.contains(filter_text));
-
-            // Check caller_method
-            let caller_method_match = line
-                .caller_method
-                .as_ref()
-                .map_or(false, |method| method.contains(filter_text));
-
-            method_match || endpoint_match || caller_class_match || caller_method_match
-        })
-        .collect()
 }
 
 // Represents a flattened navigation item (either a group header or individual message)
 #[derive(Clone, Debug)]
 enum FlatNavigationItem<'a> {
-    GroupHeader(RequestGroup),
+    // The group and the messages in it that pass the filter
+    GroupHeader(RequestGroup, &'a [&'a SqlLogMessage]),
     Message(&'a SqlLogMessage),
 }
 
 // Create a flattened navigation structure for the grouped messages
 fn create_flat_navigation_structure<'a>(
-    grouped_messages: &'a GroupedLogMessages,
+    grouped_messages: &'a GroupedLogMessages<'a>,
     expanded_groups: &std::collections::HashSet<RequestGroup>,
-    filter_text: &str,
 ) -> Vec<FlatNavigationItem<'a>> {
     let mut flat_items = Vec::new();
-    
-    for (group, messages) in &grouped_messages.groups {
-        // Filter messages within the group
-        let filtered_messages: Vec<&SqlLogMessage> = if filter_text.is_empty() {
-            messages.iter().collect()
-        } else {
-            messages.iter().filter(|msg| {
-                let method_match = if msg.http_method.is_none() {
-                    "CALL".to_lowercase().contains(&filter_text.to_lowercase())
-                } else {
-                    msg.http_method
-                        .as_ref()
-                        .map_or(false, |method| method.to_lowercase().contains(&filter_text.to_lowercase()))
-                };
-
-                let endpoint_match = msg
-                    .endpoint
-                    .as_ref()
-                    .map_or(false, |endpoint| endpoint.to_lowercase().contains(&filter_text.to_lowercase()));
+    // Depth of the collapsed tree node whose descendants are being skipped
+    let mut collapsed_depth: Option<usize> = None;
 
-                let caller_class_match = msg
-                    .caller_class
-                    .as_ref()
-                    .map_or(false, |class| class.to_lowercase().contains(&filter_text.to_lowercase()));
-
-                let caller_method_match = msg
-                    .caller_method
-                    .as_ref()
-                    .map_or(false, |method| method.to_lowercase().contains(&filter_text.to_lowercase()));
-
-                method_match || endpoint_match || caller_class_match || caller_method_match
-            }).collect()
-        };
-        
-        // Skip groups with no matching messages
-        if filtered_messages.is_empty() {
+    for (group, messages) in &grouped_messages.groups {
+        // The chronological tail lists its queries without a group header
+        if grouped_messages.mode == config::GroupingMode::Tail {
+            flat_items.extend(messages.iter().map(|msg| FlatNavigationItem::Message(msg)));
             continue;
         }
-        
-        // Add group header
-        flat_items.push(FlatNavigationItem::GroupHeader(group.clone()));
-        
-        // If group is expanded, add individual messages
-        if expanded_groups.contains(group) {
-            // Sort messages by timestamp (most recent first)
-            let mut sorted_messages = filtered_messages;
-            sorted_messages.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-            
-            for message in sorted_messages {
-                flat_items.push(FlatNavigationItem::Message(message));
+
+        let depth = group.depth();
+        if let Some(collapsed) = collapsed_depth {
+            if depth > collapsed {
+                continue;
             }
+            collapsed_depth = None;
+        }
+
+        // Add group header
+        flat_items.push(FlatNavigationItem::GroupHeader(
+            group.clone().into_owned(),
+            messages,
+        ));
+
+        // If group is expanded, add individual messages (already most recent first). Expanded
+        // tree nodes show their children instead.
+        if !expanded_groups.contains(group.as_ref()) {
+            collapsed_depth = Some(depth);
+        } else if !group.is_branch() {
+            flat_items.extend(messages.iter().map(|msg| FlatNavigationItem::Message(msg)));
         }
     }
-    
+
     flat_items
 }
 
+// Id of the message at a list selection, which counts the padding line as 0
+fn selected_message_id(flat_items: &[FlatNavigationItem], selected: usize) -> Option<u64> {
+    match flat_items.get(selected.checked_sub(1)?) {
+        Some(FlatNavigationItem::Message(message)) => Some(message.id),
+        _ => None,
+    }
+}
+
+// Where a message is in the flattened list, to follow it when the list is regrouped or resorted
+fn message_position(flat_items: &[FlatNavigationItem], id: u64) -> Option<usize> {
+    flat_items
+        .iter()
+        .position(|item| matches!(item, FlatNavigationItem::Message(msg) if msg.id == id))
+}
+
 // Count total rendered items in grouped structure for navigation
 fn count_total_rendered_items(
     grouped_messages: &GroupedLogMessages,
     expanded_groups: &std::collections::HashSet<RequestGroup>,
-    filter_text: &str,
 ) -> usize {
-    create_flat_navigation_structure(grouped_messages, expanded_groups, filter_text).len()
+    let mut count = 0;
+    let mut collapsed_depth: Option<usize> = None;
+    for (group, messages) in &grouped_messages.groups {
+        if grouped_messages.mode == config::GroupingMode::Tail {
+            count += messages.len();
+            continue;
+        }
+        let depth = group.depth();
+        if let Some(collapsed) = collapsed_depth {
+            if depth > collapsed {
+                continue;
+            }
+            collapsed_depth = None;
+        }
+        count += 1;
+        if !expanded_groups.contains(group.as_ref()) {
+            collapsed_depth = Some(depth);
+        } else if !group.is_branch() {
+            count += messages.len();
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn parse(timestamp: &str) -> SqlLogMessage {
+        let line = serde_json::json!({
+            "statement": "select 1",
+            "duration": 1,
+            "timestamp": timestamp,
+        });
+        parse_log_line(&listener::IncomingLine {
+            source: "test".to_string(),
+            line: line.to_string(),
+            decode_error: None,
+        })
+        .unwrap()
+    }
+
+    // A query run by namespace.class.method at the given second
+    fn caller_message(
+        id: u64,
+        namespace: &str,
+        class: &str,
+        method: &str,
+        second: u32,
+    ) -> SqlLogMessage {
+        let mut msg = parse(&format!("2026-01-01T00:00:{:02}Z", second));
+        msg.id = id;
+        msg.caller_namespace = Some(namespace.to_string());
+        msg.caller_class = Some(class.to_string());
+        msg.caller_method = Some(method.to_string());
+        msg
+    }
+
+    // The tree built from `messages`, one leaf per message, with the leaves in `sort` order like
+    // GroupedLogMessages passes them in. Nodes are shown as name/message count.
+    fn caller_tree(
+        messages: &[SqlLogMessage],
+        sort: sort::GroupSort,
+        pinned_groups: &HashSet<RequestGroup>,
+    ) -> Vec<String> {
+        let mut leaves: Vec<(Cow<RequestGroup>, Vec<&SqlLogMessage>)> = messages
+            .iter()
+            .map(|msg| {
+                let group = RequestGroup::from_message(msg, config::GroupingMode::Caller);
+                (Cow::Owned(group), vec![msg])
+            })
+            .collect();
+        leaves.sort_by(|a, b| compare_groups(a, b, sort, pinned_groups));
+        build_caller_tree(leaves, sort, pinned_groups)
+            .iter()
+            .map(|(group, messages)| format!("{}/{}", group.endpoint, messages.len()))
+            .collect()
+    }
+
+    fn caller_messages() -> Vec<SqlLogMessage> {
+        vec![
+            caller_message(1, "A", "X", "f", 1),
+            caller_message(2, "B", "Y", "g", 3),
+            caller_message(3, "A", "Z", "h", 2),
+            caller_message(4, "A", "X", "k", 4),
+        ]
+    }
+
+    #[test]
+    fn caller_tree_nests_methods_under_classes_and_namespaces() {
+        let tree = caller_tree(
+            &caller_messages(),
+            sort::GroupSort::default(),
+            &HashSet::new(),
+        );
+        assert_eq!(
+            tree,
+            [
+                "A/3", "X/2", "k/1", "f/1", "Z/1", "h/1", "B/1", "Y/1", "g/1"
+            ]
+        );
+    }
+
+    #[test]
+    fn caller_tree_sorts_nodes_on_the_messages_below_them() {
+        let ascending = sort::GroupSort::default().reversed();
+        let tree = caller_tree(&caller_messages(), ascending, &HashSet::new());
+        assert_eq!(
+            tree,
+            [
+                "B/1", "Y/1", "g/1", "A/3", "Z/1", "h/1", "X/2", "f/1", "k/1"
+            ]
+        );
+    }
+
+    #[test]
+    fn caller_tree_puts_pinned_nodes_first_among_their_siblings() {
+        let messages = caller_messages();
+        let pinned = HashSet::from([RequestGroup::caller_node(CallerPath {
+            namespace: "A".to_string(),
+            class: Some("Z".to_string()),
+            method: None,
+        })]);
+        let tree = caller_tree(&messages, sort::GroupSort::default(), &pinned);
+        assert_eq!(
+            tree,
+            [
+                "A/3", "Z/1", "h/1", "X/2", "k/1", "f/1", "B/1", "Y/1", "g/1"
+            ]
+        );
+    }
+
+    #[test]
+    fn timestamps_with_different_offsets_are_ordered_in_utc() {
+        // 10:30 at +02:00 is 08:30 UTC, so it comes before 09:00Z
+        let earlier = parse("2026-01-01T10:30:00+02:00");
+        let later = parse("2026-01-01T09:00:00Z");
+        assert!(earlier.time < later.time);
+        assert_eq!(
+            earlier.time,
+            chrono::Utc.with_ymd_and_hms(2026, 1, 1, 8, 30, 0).unwrap()
+        );
+        assert!(!earlier.time_is_arrival);
+    }
+
+    #[test]
+    fn dotnet_fractions_keep_their_precision() {
+        let time = parse_timestamp("2026-01-01T09:00:00.1234567+00:00").unwrap();
+        assert_eq!(time.timestamp_subsec_nanos(), 123_456_700);
+        let first = parse_timestamp("2026-01-01T09:00:00.1234567Z").unwrap();
+        let second = parse_timestamp("2026-01-01T09:00:00.1234568Z").unwrap();
+        assert!(first < second);
+    }
+
+    #[test]
+    fn naive_timestamps_are_local_time() {
+        let expected = chrono::Local
+            .with_ymd_and_hms(2026, 1, 1, 9, 0, 0)
+            .earliest()
+            .unwrap()
+            .to_utc();
+        assert_eq!(parse_timestamp("2026-01-01T09:00:00"), Some(expected));
+        assert_eq!(parse_timestamp("2026-01-01 09:00:00.000"), Some(expected));
+    }
+
+    #[test]
+    fn unparseable_timestamps_fall_back_to_arrival_time() {
+        let before = chrono::Utc::now();
+        let msg = parse("yesterday-ish");
+        assert!(msg.time_is_arrival);
+        assert!(msg.time >= before && msg.time <= chrono::Utc::now());
+    }
 }
@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+
+use serde_json::Value;
+
+use crate::SqlLogMessage;
+
+/// Parameter values sent along with a statement, either as an array for `$1`, `$2`, ... or as an
+/// object keyed by parameter name (`{"@p0": 1}` or `{"p0": 1}`).
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Parameters {
+    Positional(Vec<Value>),
+    Named(serde_json::Map<String, Value>),
+}
+
+impl Parameters {
+    /// Placeholder names with their values, in the order they were sent.
+    pub fn entries(&self) -> Vec<(String, &Value)> {
+        match self {
+            Parameters::Positional(values) => values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (format!("${}", i + 1), value))
+                .collect(),
+            Parameters::Named(values) => values
+                .iter()
+                .map(|(name, value)| (name.clone(), value))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Parameters::Positional(values) => values.is_empty(),
+            Parameters::Named(values) => values.is_empty(),
+        }
+    }
+
+    // Value for a placeholder as written in the SQL, e.g. "$2" or "@p0"
+    fn lookup(&self, placeholder: &str) -> Option<&Value> {
+        match self {
+            Parameters::Positional(values) => {
+                let position: usize = placeholder.strip_prefix('$')?.parse().ok()?;
+                values.get(position.checked_sub(1)?)
+            }
+            Parameters::Named(values) => values
+                .get(placeholder)
+                .or_else(|| values.get(&placeholder[1..])),
+        }
+    }
+}
+
+/// The statement as it should be shown and copied: with parameter values substituted when
+/// `inline` is set and the message has parameters.
+pub fn statement_text(msg: &SqlLogMessage, inline: bool) -> Cow<'_, str> {
+    match &msg.parameters {
+        Some(parameters) if inline && !parameters.is_empty() => {
+            Cow::Owned(inline_parameters(&msg.statement, parameters))
+        }
+        _ => Cow::Borrowed(&msg.statement),
+    }
+}
+
+/// A JSON value written as a Postgres literal that can be pasted into psql.
+pub fn postgres_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(true) => "TRUE".to_string(),
+        Value::Bool(false) => "FALSE".to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => quote_literal(text),
+        // An empty ARRAY[] has no element type, so fall back to the untyped array literal
+        Value::Array(items) if items.is_empty() => "'{}'".to_string(),
+        Value::Array(items) => format!(
+            "ARRAY[{}]",
+            items
+                .iter()
+                .map(postgres_literal)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Object(_) => quote_literal(&value.to_string()),
+    }
+}
+
+// Single quotes doubled, which is all standard_conforming_strings needs
+fn quote_literal(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Replace `$n`, `@name` and `:name` placeholders with the literal of their value. String
+/// literals (including `E'...'` escape strings), quoted identifiers, dollar-quoted bodies and
+/// comments are left alone, as are placeholders without a value.
+pub fn inline_parameters(statement: &str, parameters: &Parameters) -> String {
+    let chars: Vec<char> = statement.chars().collect();
+    let mut out = String::with_capacity(statement.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            '\'' | '"' => {
+                let end = find_closing_quote(&chars, i + 1, c, false);
+                out.extend(&chars[i..end]);
+                i = end;
+            }
+            // Escape string, E'...', unless the E ends an identifier
+            'E' | 'e'
+                if next == Some('\'')
+                    && !out.ends_with(|p: char| p.is_alphanumeric() || p == '_') =>
+            {
+                let end = find_closing_quote(&chars, i + 2, '\'', true);
+                out.extend(&chars[i..end]);
+                i = end;
+            }
+            '-' if next == Some('-') => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|&c| c == '\n')
+                    .map_or(chars.len(), |offset| i + offset);
+                out.extend(&chars[i..end]);
+                i = end;
+            }
+            '/' if next == Some('*') => {
+                let end = (i + 2..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                    .map_or(chars.len(), |j| j + 2);
+                out.extend(&chars[i..end]);
+                i = end;
+            }
+            // A cast like ::int, not a placeholder
+            ':' if next == Some(':') => {
+                out.push_str("::");
+                i += 2;
+            }
+            // Part of an identifier such as a$1 is not a placeholder either
+            '$' | '@' | ':' if !out.ends_with(|p: char| p.is_alphanumeric() || p == '_') => {
+                let name_end = (i + 1..chars.len())
+                    .find(|&j| !is_name_char(c, chars[j]))
+                    .unwrap_or(chars.len());
+                if c == '$' && name_end == i + 1 {
+                    // Not $n, so possibly the start of a dollar-quoted body
+                    let end = skip_dollar_quote(&chars, i);
+                    out.extend(&chars[i..end]);
+                    i = end;
+                    continue;
+                }
+                let placeholder: String = chars[i..name_end].iter().collect();
+                match parameters.lookup(&placeholder) {
+                    Some(value) if name_end > i + 1 => out.push_str(&postgres_literal(value)),
+                    _ => out.push_str(&placeholder),
+                }
+                i = name_end;
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// $ is followed by a position, @ and : by a name
+fn is_name_char(sigil: char, c: char) -> bool {
+    if sigil == '$' {
+        c.is_ascii_digit()
+    } else {
+        c.is_alphanumeric() || c == '_'
+    }
+}
+
+// Index just past the closing quote, where a doubled quote is an escaped one, and in escape strings
+// so is anything after a backslash
+fn find_closing_quote(chars: &[char], mut i: usize, quote: char, backslash_escapes: bool) -> usize {
+    while i < chars.len() {
+        if backslash_escapes && chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+// Index just past a $tag$...$tag$ body starting at `start`, or past the lone '$' if there is none
+fn skip_dollar_quote(chars: &[char], start: usize) -> usize {
+    let tag_end =
+        (start + 1..chars.len()).find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_'));
+    let Some(tag_end) = tag_end.filter(|&j| chars[j] == '$') else {
+        return start + 1;
+    };
+    let tag = &chars[start..=tag_end];
+    (tag_end + 1..chars.len())
+        .find(|&j| chars[j..].starts_with(tag))
+        .map_or(chars.len(), |j| j + tag.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parameters(values: Value) -> Parameters {
+        serde_json::from_value(values).unwrap()
+    }
+
+    #[test]
+    fn positional_placeholders_are_replaced() {
+        let params = parameters(json!([42, "bob", null]));
+        assert_eq!(
+            inline_parameters(
+                "select * from t where id = $1 and name = $2 or x is $3",
+                &params
+            ),
+            "select * from t where id = 42 and name = 'bob' or x is NULL"
+        );
+    }
+
+    #[test]
+    fn multi_digit_positions_are_read_whole() {
+        let params = parameters(json!([1, 2, 3, 4, 5, 6, 7, 8, 9, 10]));
+        assert_eq!(inline_parameters("select $10, $1", &params), "select 10, 1");
+    }
+
+    #[test]
+    fn named_placeholders_with_or_without_sigil() {
+        let params = parameters(json!({"@p0": 1, "name": "x"}));
+        assert_eq!(
+            inline_parameters("select @p0, :name, @missing", &params),
+            "select 1, 'x', @missing"
+        );
+    }
+
+    #[test]
+    fn string_literals_are_quoted_and_escaped() {
+        let params = parameters(json!(["it's", true, [1, 2], []]));
+        assert_eq!(
+            inline_parameters("select $1, $2, $3, $4", &params),
+            "select 'it''s', TRUE, ARRAY[1, 2], '{}'"
+        );
+    }
+
+    #[test]
+    fn quoted_text_is_left_alone() {
+        let params = parameters(json!([1]));
+        assert_eq!(
+            inline_parameters(
+                r#"select '$1', 'it''s $1', "$1" from t where a = $1"#,
+                &params
+            ),
+            r#"select '$1', 'it''s $1', "$1" from t where a = 1"#
+        );
+    }
+
+    #[test]
+    fn escape_strings_are_left_alone() {
+        let params = parameters(json!([1]));
+        assert_eq!(
+            inline_parameters(r"select E'it\'s $1', e'\\' , $1", &params),
+            r"select E'it\'s $1', e'\\' , 1"
+        );
+        // A word ending in e is not an escape string prefix
+        assert_eq!(
+            inline_parameters("select * from t where type='$1' and x = $1", &params),
+            "select * from t where type='$1' and x = 1"
+        );
+    }
+
+    #[test]
+    fn dollar_quoted_bodies_are_left_alone() {
+        let params = parameters(json!([1]));
+        assert_eq!(
+            inline_parameters("do $$ select $1 $$; do $fn$ $1 $fn$; select $1", &params),
+            "do $$ select $1 $$; do $fn$ $1 $fn$; select 1"
+        );
+    }
+
+    #[test]
+    fn comments_are_left_alone() {
+        let params = parameters(json!([1]));
+        assert_eq!(
+            inline_parameters("select $1 -- uses $1\n/* $1 */ from t", &params),
+            "select 1 -- uses $1\n/* $1 */ from t"
+        );
+    }
+
+    #[test]
+    fn casts_and_identifiers_are_not_placeholders() {
+        let params = parameters(json!({"int": 5, "p": 2}));
+        assert_eq!(
+            inline_parameters("select :p::int, a:p, col$1", &params),
+            "select 2::int, a:p, col$1"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::mpsc,
+    time::Duration,
+};
+
+use crate::listener::IncomingLine;
+
+/// One line of a recording file: the raw logger line plus where and when it arrived.
+#[derive(Serialize, Deserialize, Debug)]
+struct RecordedLine {
+    received_at: DateTime<Local>,
+    source: String,
+    line: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    decode_error: Option<String>,
+    // When the recording session started. Several sessions can be appended to one file, and
+    // replay does not wait out the time between them. Missing in older recordings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session: Option<DateTime<Local>>,
+}
+
+/// How fast a recording is fed back into the monitor.
+#[derive(Debug, Clone, Copy)]
+pub enum ReplayPace {
+    /// Send every line immediately
+    Instant,
+    /// Keep the original gaps between lines, divided by the speed multiplier
+    Original { speed: f64 },
+}
+
+/// Append every incoming line to `path` before forwarding it to `tx`.
+/// Returns the sender the listeners should use instead of `tx`.
+pub fn spawn_recorder(
+    path: &Path,
+    tx: mpsc::Sender<IncomingLine>,
+) -> anyhow::Result<mpsc::Sender<IncomingLine>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("failed to open recording {}: {}", path.display(), e))?;
+    let mut writer = LineWriter::new(file);
+    let (record_tx, record_rx) = mpsc::channel::<IncomingLine>();
+    let session = Local::now();
+
+    std::thread::spawn(move || {
+        for incoming in record_rx {
+            let recorded = RecordedLine {
+                received_at: Local::now(),
+                source: incoming.source.clone(),
+                line: incoming.line.clone(),
+                decode_error: incoming.decode_error.clone(),
+                session: Some(session),
+            };
+            // A failing disk should not stop the monitor, so write errors are ignored
+            if let Ok(json) = serde_json::to_string(&recorded) {
+                let _ = writeln!(writer, "{}", json);
+            }
+            if tx.send(incoming).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(record_tx)
+}
+
+/// Feed a recording into `tx` on a background thread. Lines that are not in the recording format
+/// are sent as they are, so plain logger output can be replayed too.
+pub fn spawn_replay(
+    path: &Path,
+    pace: ReplayPace,
+    tx: mpsc::Sender<IncomingLine>,
+) -> anyhow::Result<()> {
+    let file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("failed to open recording {}: {}", path.display(), e))?;
+    let fallback_source = format!("replay:{}", path.display());
+
+    std::thread::spawn(move || {
+        let mut previous: Option<(Option<DateTime<Local>>, DateTime<Local>)> = None;
+        for line in BufReader::new(file).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let incoming = match serde_json::from_str::<RecordedLine>(&line) {
+                Ok(recorded) => {
+                    if let ReplayPace::Original { speed } = pace {
+                        // The first line of an appended session follows right away
+                        if let Some((session, received_at)) = previous
+                            && session == recorded.session
+                        {
+                            let gap = (recorded.received_at - received_at)
+                                .to_std()
+                                .unwrap_or(Duration::ZERO);
+                            // A speed close to zero stretches the gap past what a Duration
+                            // holds, which div_f64 would panic on
+                            let paced = Duration::try_from_secs_f64(gap.as_secs_f64() / speed)
+                                .unwrap_or(Duration::MAX);
+                            std::thread::sleep(paced);
+                        }
+                        previous = Some((recorded.session, recorded.received_at));
+                    }
+                    IncomingLine {
+                        source: recorded.source,
+                        line: recorded.line,
+                        decode_error: recorded.decode_error,
+                    }
+                }
+                Err(_) => IncomingLine {
+                    source: fallback_source.clone(),
+                    line,
+                    decode_error: None,
+                },
+            };
+            if tx.send(incoming).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use std::cmp::Ordering;
+
+use crate::{RequestGroup, SqlLogMessage};
+
+/// What groups, and the messages in them, are ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Most recent message
+    #[default]
+    Recent,
+    /// Sum of the query durations
+    TotalDuration,
+    /// Slowest query
+    MaxDuration,
+    /// Number of queries
+    Count,
+    /// Endpoint and method for groups, statement text for messages
+    Name,
+}
+
+/// A sort key plus direction. Pinned groups stay on top whatever the sort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupSort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Default for GroupSort {
+    fn default() -> Self {
+        GroupSort {
+            key: SortKey::Recent,
+            descending: true,
+        }
+    }
+}
+
+impl GroupSort {
+    /// The next sort key, in the direction that reads naturally for it: largest first for the
+    /// numbers, A to Z for names.
+    pub fn next(self) -> Self {
+        let key = match self.key {
+            SortKey::Recent => SortKey::TotalDuration,
+            SortKey::TotalDuration => SortKey::MaxDuration,
+            SortKey::MaxDuration => SortKey::Count,
+            SortKey::Count => SortKey::Name,
+            SortKey::Name => SortKey::Recent,
+        };
+        GroupSort {
+            key,
+            descending: key != SortKey::Name,
+        }
+    }
+
+    pub fn reversed(self) -> Self {
+        GroupSort {
+            descending: !self.descending,
+            ..self
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let key = match self.key {
+            SortKey::Recent => "recent",
+            SortKey::TotalDuration => "total time",
+            SortKey::MaxDuration => "max time",
+            SortKey::Count => "count",
+            SortKey::Name => "name",
+        };
+        format!("{} {}", key, if self.descending { "↓" } else { "↑" })
+    }
+
+    /// Order two groups by the messages in them.
+    pub fn compare_groups(
+        &self,
+        (a, a_messages): (&RequestGroup, &[&SqlLogMessage]),
+        (b, b_messages): (&RequestGroup, &[&SqlLogMessage]),
+    ) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Recent => latest(a_messages).cmp(&latest(b_messages)),
+            SortKey::TotalDuration => total_ms(a_messages).cmp(&total_ms(b_messages)),
+            SortKey::MaxDuration => max_ms(a_messages).cmp(&max_ms(b_messages)),
+            SortKey::Count => a_messages.len().cmp(&b_messages.len()),
+            SortKey::Name => (&a.endpoint, &a.http_method).cmp(&(&b.endpoint, &b.http_method)),
+        };
+        self.direct(ordering)
+    }
+
+    /// Order messages within a group. Both duration keys compare the query's own duration, and
+    /// count, which means nothing for a single query, falls back to recency.
+    pub fn sort_messages(&self, messages: &mut [&SqlLogMessage]) {
+        messages.sort_by(|a, b| {
+            let ordering = match self.key {
+                SortKey::Recent | SortKey::Count => (a.time, a.id).cmp(&(b.time, b.id)),
+                SortKey::TotalDuration | SortKey::MaxDuration => a.duration.cmp(&b.duration),
+                SortKey::Name => a.statement.cmp(&b.statement),
+            };
+            self.direct(ordering)
+        });
+    }
+
+    fn direct(&self, ordering: Ordering) -> Ordering {
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+fn latest(messages: &[&SqlLogMessage]) -> Option<chrono::DateTime<chrono::Utc>> {
+    messages.iter().map(|msg| msg.time).max()
+}
+
+fn total_ms(messages: &[&SqlLogMessage]) -> u64 {
+    messages.iter().map(|msg| msg.duration).sum()
+}
+
+fn max_ms(messages: &[&SqlLogMessage]) -> u64 {
+    messages.iter().map(|msg| msg.duration).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GroupingMode;
+
+    fn message(id: u64, statement: &str, duration: u64, second: u32) -> SqlLogMessage {
+        let timestamp = format!("2026-01-01T00:00:{:02}Z", second);
+        let mut msg: SqlLogMessage = serde_json::from_value(serde_json::json!({
+            "statement": statement,
+            "duration": duration,
+            "timestamp": timestamp,
+            "endpoint": format!("/{}", statement),
+            "http_method": "GET",
+        }))
+        .unwrap();
+        msg.id = id;
+        msg.time = timestamp.parse().unwrap();
+        msg
+    }
+
+    fn sort(key: SortKey, descending: bool) -> GroupSort {
+        GroupSort { key, descending }
+    }
+
+    #[test]
+    fn next_cycles_through_every_key_in_its_natural_direction() {
+        let mut current = GroupSort::default();
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            seen.push((current.key, current.descending));
+            current = current.next();
+        }
+        assert_eq!(current, GroupSort::default());
+        assert_eq!(
+            seen,
+            [
+                (SortKey::Recent, true),
+                (SortKey::TotalDuration, true),
+                (SortKey::MaxDuration, true),
+                (SortKey::Count, true),
+                (SortKey::Name, false),
+            ]
+        );
+        // A reversed sort starts over in the natural direction of the next key
+        assert!(GroupSort::default().reversed().next().descending);
+    }
+
+    #[test]
+    fn labels_show_the_direction() {
+        assert_eq!(GroupSort::default().label(), "recent ↓");
+        assert_eq!(sort(SortKey::Name, false).label(), "name ↑");
+    }
+
+    #[test]
+    fn groups_compare_on_their_messages() {
+        // a: two quick queries, early; b: one slow query, later
+        let a1 = message(1, "a", 10, 1);
+        let a2 = message(2, "a", 20, 2);
+        let b1 = message(3, "b", 25, 3);
+        let group_a = RequestGroup::from_message(&a1, GroupingMode::Endpoint);
+        let group_b = RequestGroup::from_message(&b1, GroupingMode::Endpoint);
+        let a = (&group_a, &[&a1, &a2][..]);
+        let b = (&group_b, &[&b1][..]);
+
+        let ascending = |key| sort(key, false).compare_groups(a, b);
+        assert_eq!(ascending(SortKey::Recent), Ordering::Less);
+        assert_eq!(ascending(SortKey::TotalDuration), Ordering::Greater);
+        assert_eq!(ascending(SortKey::MaxDuration), Ordering::Less);
+        assert_eq!(ascending(SortKey::Count), Ordering::Greater);
+        assert_eq!(ascending(SortKey::Name), Ordering::Less);
+        assert_eq!(
+            sort(SortKey::Count, true).compare_groups(a, b),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn messages_sort_on_their_own_values() {
+        let first = message(1, "b", 30, 1);
+        let second = message(2, "c", 10, 2);
+        // Same time as second, so the id breaks the tie
+        let third = message(3, "a", 20, 2);
+        let ids = |sort: GroupSort| {
+            let mut messages = vec![&second, &third, &first];
+            sort.sort_messages(&mut messages);
+            messages.iter().map(|msg| msg.id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(GroupSort::default()), [3, 2, 1]);
+        assert_eq!(ids(sort(SortKey::Count, false)), [1, 2, 3]);
+        assert_eq!(ids(sort(SortKey::TotalDuration, true)), [1, 3, 2]);
+        assert_eq!(ids(sort(SortKey::MaxDuration, false)), [2, 3, 1]);
+        assert_eq!(ids(sort(SortKey::Name, false)), [3, 1, 2]);
+    }
+}
@@ -0,0 +1,487 @@
+use chrono::{DateTime, Utc};
+use ratatui::style::Color;
+use std::collections::HashMap;
+
+use crate::{
+    RequestGroup, SqlLogMessage,
+    config::{self, GroupingMode},
+    format::detect_query_badge,
+};
+
+/// Aggregated timings for all retained queries sharing one fingerprint.
+#[derive(Debug, Clone)]
+pub struct FingerprintStats {
+    pub fingerprint: String,
+    pub calls: usize,
+    pub total_ms: u64,
+    pub mean_ms: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    // Row counts, when the logger reports them for any call of this fingerprint
+    pub mean_rows: Option<f64>,
+    pub max_rows: Option<u64>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Aggregate messages per fingerprint, ordered by total time spent (highest first).
+pub fn fingerprint_stats<'a>(
+    messages: impl IntoIterator<Item = &'a SqlLogMessage>,
+) -> Vec<FingerprintStats> {
+    let mut by_fingerprint: HashMap<&str, Vec<&SqlLogMessage>> = HashMap::new();
+    for msg in messages {
+        by_fingerprint
+            .entry(msg.fingerprint.as_str())
+            .or_default()
+            .push(msg);
+    }
+
+    let mut stats: Vec<FingerprintStats> = by_fingerprint
+        .into_iter()
+        .map(|(fingerprint, messages)| {
+            let mut durations: Vec<u64> = messages.iter().map(|msg| msg.duration).collect();
+            durations.sort_unstable();
+            let total_ms: u64 = durations.iter().sum();
+            let rows: Vec<u64> = messages.iter().filter_map(|msg| msg.rows()).collect();
+            let last_seen = messages
+                .iter()
+                .map(|msg| msg.time)
+                .max()
+                .unwrap_or_default();
+            FingerprintStats {
+                fingerprint: fingerprint.to_string(),
+                calls: durations.len(),
+                total_ms,
+                mean_ms: total_ms as f64 / durations.len() as f64,
+                p50_ms: percentile(&durations, 50.0),
+                p95_ms: percentile(&durations, 95.0),
+                p99_ms: percentile(&durations, 99.0),
+                max_ms: durations.last().copied().unwrap_or(0),
+                mean_rows: (!rows.is_empty())
+                    .then(|| rows.iter().sum::<u64>() as f64 / rows.len() as f64),
+                max_rows: rows.iter().max().copied(),
+                last_seen,
+            }
+        })
+        .collect();
+
+    stats.sort_by(|a, b| {
+        b.total_ms
+            .cmp(&a.total_ms)
+            .then_with(|| a.fingerprint.cmp(&b.fingerprint))
+    });
+    stats
+}
+
+/// Nearest-rank percentile of an ascending list of durations.
+pub fn percentile(sorted: &[u64], pct: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Aggregates shown in a group header.
+#[derive(Debug, Clone)]
+pub struct GroupSummary {
+    pub total_ms: u64,
+    pub mean_ms: u64,
+    pub max_ms: u64,
+    pub error_count: usize,
+    // Durations of the most recent queries, oldest first
+    pub recent_ms: Vec<u64>,
+}
+
+/// Total, mean and max duration and error count of a group's messages, plus the durations of the
+/// last `recent` queries by timestamp.
+pub fn summarize_group(messages: &[&SqlLogMessage], recent: usize) -> GroupSummary {
+    let total_ms: u64 = messages.iter().map(|msg| msg.duration).sum();
+    let mut by_time: Vec<&SqlLogMessage> = messages.to_vec();
+    by_time.sort_by_key(|msg| (msg.time, msg.id));
+    GroupSummary {
+        total_ms,
+        mean_ms: total_ms / messages.len().max(1) as u64,
+        max_ms: messages.iter().map(|msg| msg.duration).max().unwrap_or(0),
+        error_count: messages.iter().filter(|msg| msg.is_error()).count(),
+        recent_ms: by_time[by_time.len().saturating_sub(recent)..]
+            .iter()
+            .map(|msg| msg.duration)
+            .collect(),
+    }
+}
+
+/// What one request instance spent in the database.
+#[derive(Debug, Clone)]
+pub struct RequestSummary {
+    pub db_ms: u64,
+    // From the start of the first query to the end of the last one
+    pub span_ms: u64,
+}
+
+/// Total query time and wall-clock span of a group's messages, taking each timestamp as the
+/// start of its query.
+pub fn summarize_request(messages: &[&SqlLogMessage]) -> RequestSummary {
+    let db_ms = messages
+        .iter()
+        .fold(0u64, |total, msg| total.saturating_add(msg.duration));
+    let start = messages.iter().map(|msg| msg.time).min();
+    // Durations come straight from the logger, so one too large for a timestamp ends the span at
+    // the latest representable time instead of panicking
+    let end = messages
+        .iter()
+        .map(|msg| {
+            chrono::TimeDelta::try_milliseconds(i64::try_from(msg.duration).unwrap_or(i64::MAX))
+                .and_then(|duration| msg.time.checked_add_signed(duration))
+                .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC)
+        })
+        .max();
+    let span_ms = match (start, end) {
+        (Some(start), Some(end)) => (end - start).num_milliseconds().max(0) as u64,
+        _ => 0,
+    };
+    RequestSummary { db_ms, span_ms }
+}
+
+/// A burst of identical statements from one caller, the typical N+1 query pattern.
+#[derive(Debug, Clone)]
+pub struct NPlusOne {
+    pub fingerprint: String,
+    pub count: usize,
+}
+
+// Fingerprint plus caller class and method
+type CallSiteKey<'a> = (&'a str, Option<&'a str>, Option<&'a str>);
+
+/// Find the largest burst of queries with the same fingerprint and caller that arrived within the
+/// configured window. Returns None when no burst reaches the configured threshold.
+pub fn detect_n_plus_one(messages: &[&SqlLogMessage]) -> Option<NPlusOne> {
+    let config = config::get();
+    let mut by_key: HashMap<CallSiteKey, Vec<i64>> = HashMap::new();
+    for msg in messages {
+        by_key
+            .entry((
+                msg.fingerprint.as_str(),
+                msg.caller_class.as_deref(),
+                msg.caller_method.as_deref(),
+            ))
+            .or_default()
+            .push(msg.time.timestamp_millis());
+    }
+
+    let window_ms = config.n_plus_one_window_ms as i64;
+    let mut worst: Option<NPlusOne> = None;
+    for ((fingerprint, _, _), mut times) in by_key {
+        if times.len() < config.n_plus_one_threshold {
+            continue;
+        }
+        times.sort_unstable();
+        // Sliding window over the sorted arrival times
+        let mut start = 0;
+        let mut best = 0;
+        for end in 0..times.len() {
+            while times[end] - times[start] > window_ms {
+                start += 1;
+            }
+            best = best.max(end - start + 1);
+        }
+        if best >= config.n_plus_one_threshold && worst.as_ref().is_none_or(|w| best > w.count) {
+            worst = Some(NPlusOne {
+                fingerprint: fingerprint.to_string(),
+                count: best,
+            });
+        }
+    }
+    worst
+}
+
+/// Shorten a fingerprint for one-line display, eliding the select list.
+pub fn summarize_fingerprint(fingerprint: &str) -> String {
+    if let Some(rest) = fingerprint.strip_prefix("select ")
+        && let Some(from_index) = rest.find(" from ")
+    {
+        return format!("select …{}", &rest[from_index..]);
+    }
+    fingerprint.to_string()
+}
+
+/// Aggregates drawn on the dashboard screen.
+pub struct Dashboard<'a> {
+    // Queries and database time per second, oldest first, the last one ending at window_end
+    pub queries_per_second: Vec<u64>,
+    pub db_ms_per_second: Vec<u64>,
+    pub window_end: DateTime<Utc>,
+    // Number of queries per duration bucket
+    pub histogram: Vec<DurationBucket>,
+    // Number of queries per statement type, most frequent first
+    pub statement_types: Vec<StatementType>,
+    pub slowest: Vec<&'a SqlLogMessage>,
+    pub noisiest: Vec<EndpointActivity>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DurationBucket {
+    pub label: &'static str,
+    // Lower bound, used to colour the bucket
+    pub min_ms: u64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatementType {
+    pub label: String,
+    pub color: Color,
+    pub count: u64,
+}
+
+/// Query count and time of one endpoint + method group.
+#[derive(Debug, Clone)]
+pub struct EndpointActivity {
+    pub label: String,
+    pub count: usize,
+    pub total_ms: u64,
+}
+
+// Lower bounds of the histogram buckets, each running up to the next one
+const DURATION_BUCKETS: [(&str, u64); 8] = [
+    ("<10ms", 0),
+    ("10ms", 10),
+    ("50ms", 50),
+    ("100ms", 100),
+    ("250ms", 250),
+    ("500ms", 500),
+    ("1s", 1000),
+    ("5s+", 5000),
+];
+
+/// Number of queries listed in the dashboard's top-N tables.
+const DASHBOARD_TOP: usize = 10;
+
+/// Aggregate the retained messages for the dashboard. Only the rate series is limited to
+/// `window`, everything else covers all retained messages.
+pub fn dashboard<'a>(
+    messages: impl IntoIterator<Item = &'a SqlLogMessage>,
+    window: std::time::Duration,
+) -> Dashboard<'a> {
+    let messages: Vec<&SqlLogMessage> = messages.into_iter().collect();
+
+    // A live window ends now, but one that would be empty (a replayed recording, or a quiet
+    // application) ends at the last query instead so there is something to see
+    let window_secs = window.as_secs().max(1) as usize;
+    let latest = messages.iter().map(|msg| msg.time).max();
+    let now = Utc::now();
+    let window_end = match latest {
+        Some(latest) if (now - latest).num_seconds() >= window_secs as i64 => latest,
+        _ => now,
+    };
+    let mut queries_per_second = vec![0; window_secs];
+    let mut db_ms_per_second = vec![0; window_secs];
+    for msg in &messages {
+        let age = (window_end - msg.time).num_seconds();
+        if (0..window_secs as i64).contains(&age) {
+            let slot = window_secs - 1 - age as usize;
+            queries_per_second[slot] += 1;
+            db_ms_per_second[slot] += msg.duration;
+        }
+    }
+
+    let mut histogram: Vec<DurationBucket> = DURATION_BUCKETS
+        .iter()
+        .map(|&(label, min_ms)| DurationBucket {
+            label,
+            min_ms,
+            count: 0,
+        })
+        .collect();
+    for msg in &messages {
+        if let Some(bucket) = histogram
+            .iter_mut()
+            .rev()
+            .find(|bucket| msg.duration >= bucket.min_ms)
+        {
+            bucket.count += 1;
+        }
+    }
+
+    let mut statement_types: Vec<StatementType> = Vec::new();
+    for msg in &messages {
+        let (label, color) = statement_type(&msg.statement);
+        match statement_types.iter_mut().find(|kind| kind.label == label) {
+            Some(kind) => kind.count += 1,
+            None => statement_types.push(StatementType {
+                label,
+                color,
+                count: 1,
+            }),
+        }
+    }
+    statement_types.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.label.cmp(&b.label)));
+
+    let mut slowest = messages.clone();
+    slowest.sort_by(|a, b| {
+        b.duration
+            .cmp(&a.duration)
+            .then_with(|| b.time.cmp(&a.time))
+    });
+    slowest.truncate(DASHBOARD_TOP);
+
+    let mut by_endpoint: HashMap<String, EndpointActivity> = HashMap::new();
+    for msg in &messages {
+        let group = RequestGroup::from_message(msg, GroupingMode::Endpoint);
+        let label = format!("{} {}", group.http_method, group.endpoint);
+        let activity = by_endpoint
+            .entry(label.clone())
+            .or_insert(EndpointActivity {
+                label,
+                count: 0,
+                total_ms: 0,
+            });
+        activity.count += 1;
+        activity.total_ms += msg.duration;
+    }
+    let mut noisiest: Vec<EndpointActivity> = by_endpoint.into_values().collect();
+    noisiest.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| b.total_ms.cmp(&a.total_ms))
+            .then_with(|| a.label.cmp(&b.label))
+    });
+    noisiest.truncate(DASHBOARD_TOP);
+
+    Dashboard {
+        queries_per_second,
+        db_ms_per_second,
+        window_end,
+        histogram,
+        statement_types,
+        slowest,
+        noisiest,
+    }
+}
+
+// The query badge label and colour, with plain SELECTs (which get no badge) and anything else
+// counted apart
+fn statement_type(sql: &str) -> (String, Color) {
+    if let Some(badge) = detect_query_badge(sql) {
+        return (badge.label, badge.bg_color);
+    }
+    let first_word = sql
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    match first_word.as_str() {
+        "select" | "with" => ("SELECT".to_string(), Color::Rgb(97, 175, 254)),
+        _ => ("OTHER".to_string(), Color::Rgb(128, 128, 128)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(statement: &str, duration: u64, time: &str) -> SqlLogMessage {
+        let mut msg: SqlLogMessage = serde_json::from_value(serde_json::json!({
+            "statement": statement,
+            "duration": duration,
+            "timestamp": time,
+        }))
+        .unwrap();
+        msg.time = time.parse().unwrap();
+        msg.fingerprint = crate::fingerprint::fingerprint_sql(statement);
+        msg
+    }
+
+    // `count` identical statements from one caller, `gap_ms` apart, starting at `start_ms`
+    fn burst(statement: &str, count: usize, start_ms: i64, gap_ms: i64) -> Vec<SqlLogMessage> {
+        (0..count as i64)
+            .map(|i| {
+                let mut msg = message(statement, 1, "2026-01-01T00:00:00Z");
+                msg.time += chrono::TimeDelta::milliseconds(start_ms + i * gap_ms);
+                msg.caller_class = Some("OrderRepo".to_string());
+                msg.caller_method = Some("Load".to_string());
+                msg
+            })
+            .collect()
+    }
+
+    fn detect(messages: &[SqlLogMessage]) -> Option<usize> {
+        let messages: Vec<&SqlLogMessage> = messages.iter().collect();
+        detect_n_plus_one(&messages).map(|n_plus_one| n_plus_one.count)
+    }
+
+    #[test]
+    fn n_plus_one_needs_the_threshold() {
+        let threshold = config::get().n_plus_one_threshold;
+        assert_eq!(
+            detect(&burst(
+                "select * from items where id = 1",
+                threshold - 1,
+                0,
+                10
+            )),
+            None
+        );
+        assert_eq!(
+            detect(&burst("select * from items where id = 1", threshold, 0, 10)),
+            Some(threshold)
+        );
+    }
+
+    #[test]
+    fn n_plus_one_survives_unrelated_statements_in_between() {
+        let threshold = config::get().n_plus_one_threshold;
+        let half = threshold / 2;
+        let mut messages = burst("select * from items where id = 1", half, 0, 10);
+        messages.extend(burst("select * from orders", 1, 60, 0));
+        messages.extend(burst(
+            "select * from items where id = 2",
+            threshold - half,
+            100,
+            10,
+        ));
+        assert_eq!(detect(&messages), Some(threshold));
+    }
+
+    #[test]
+    fn n_plus_one_counts_only_what_fits_in_the_window() {
+        let config = config::get();
+        let threshold = config.n_plus_one_threshold;
+        let window_ms = config.n_plus_one_window_ms as i64;
+        // Spread so that only half of them fit in any window
+        let gap_ms = window_ms / (threshold as i64 / 2 - 1) + 1;
+        assert_eq!(detect(&burst("select 1", threshold, 0, gap_ms)), None);
+        // Spaced so that exactly the threshold fits in one window
+        let gap_ms = window_ms / (threshold as i64 - 1);
+        assert_eq!(
+            detect(&burst("select 1", threshold, 0, gap_ms)),
+            Some(threshold)
+        );
+        assert_eq!(
+            detect(&burst("select 1", threshold * 2, 0, gap_ms)),
+            Some(threshold)
+        );
+    }
+
+    #[test]
+    fn request_span_runs_from_first_start_to_last_end() {
+        let first = message("select 1", 10, "2026-01-01T00:00:00Z");
+        let second = message("select 2", 50, "2026-01-01T00:00:00.100Z");
+        let summary = summarize_request(&[&first, &second]);
+        assert_eq!(summary.db_ms, 60);
+        assert_eq!(summary.span_ms, 150);
+    }
+
+    #[test]
+    fn huge_durations_do_not_panic() {
+        let at = "2026-01-01T00:00:00Z";
+        for duration in [u64::MAX, i64::MAX as u64, 1 << 60] {
+            let huge = message("select 1", duration, at);
+            let other = message("select 2", 5, at);
+            let summary = summarize_request(&[&huge, &other]);
+            assert_eq!(summary.db_ms, duration.saturating_add(5));
+            assert!(summary.span_ms > 0);
+        }
+    }
+}
@@ -1,54 +1,47 @@
+use chrono::{DateTime, Local, Utc};
 use ratatui::{
     style::{Color, Style},
     text::{Line, Span},
 };
-use chrono::{DateTime, Local, Utc};
+use std::{collections::HashMap, rc::Rc};
 
 use crate::{
-    SqlLogMessage, RequestGroup, GroupedLogMessages,
-    format::{classify_sql_size, highlight_sql, sql_size_color, detect_query_badge},
+    FlatNavigationItem, GroupedLogMessages, RejectedLines, RequestGroup, SqlLogMessage,
+    format::{
+        classify_sql_size, detect_query_badge, format_count, highlight_sql, row_count_color,
+        sql_size_color,
+    },
+    params::{self, Parameters},
+    stats::{Dashboard, FingerprintStats, GroupSummary, NPlusOne, RequestSummary},
 };
 
-// Helper function to extract HH:MM:SS from timestamp and convert to local time
-fn extract_time_from_timestamp(timestamp: &str) -> String {
-    // Try to parse as RFC3339/ISO 8601 format and convert to local time
-    if let Ok(dt) = DateTime::parse_from_rfc3339(timestamp) {
-        let local_time: DateTime<Local> = dt.with_timezone(&Local);
-        return local_time.format("%H:%M:%S").to_string();
-    }
-    
-    // Try to parse as UTC timestamp without timezone info
-    if let Ok(dt) = timestamp.parse::<DateTime<Utc>>() {
-        let local_time: DateTime<Local> = dt.with_timezone(&Local);
-        return local_time.format("%H:%M:%S").to_string();
-    }
-    
-    // Try to extract time portion from various timestamp formats as fallback
-    if let Some(time_part) = timestamp.split('T').nth(1) {
-        // ISO format like "2023-12-01T14:30:25.123Z"
-        if let Some(time_only) = time_part.split('.').next() {
-            return time_only.to_string();
-        }
-    }
-    
-    // If timestamp contains space, try splitting on space
-    if let Some(time_part) = timestamp.split(' ').nth(1) {
-        if let Some(time_only) = time_part.split('.').next() {
-            return time_only.to_string();
-        }
+// HH:MM:SS in local time
+pub fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%H:%M:%S").to_string()
+}
+
+// Time of a message, marked with ~ when the logger's timestamp could not be parsed and the
+// arrival time is shown instead
+pub fn message_time(msg: &SqlLogMessage) -> String {
+    if msg.time_is_arrival {
+        format!("~{}", format_time(msg.time))
+    } else {
+        format_time(msg.time)
     }
-    
-    // Fallback - just return the timestamp as is
-    timestamp.to_string()
 }
 
 // Helper function for rendering the header row
+#[allow(clippy::too_many_arguments)]
 pub fn render_header_row(
     arrow: &str,
     formatted_duration: &str,
     sql_len: usize,
     sql_color: Color,
     time_str: &str,
+    rows: (Option<u64>, Option<u64>),
+    source: Option<&str>,
+    is_bookmarked: bool,
+    error: Option<Option<&str>>,
     sql_statement: &str,
     is_flashing: bool,
     flash_bg: Color,
@@ -83,12 +76,88 @@ pub fn render_header_row(
         if is_flashing {
             Style::default().bg(flash_bg).fg(flash_fg)
         } else {
-            Style::default().bg(Color::Rgb(100, 100, 100)).fg(Color::White)
+            Style::default()
+                .bg(Color::Rgb(100, 100, 100))
+                .fg(Color::White)
         },
     ));
 
     let mut used_width = arrow_duration_text.len() + char_count_text.len() + time_text.len();
 
+    // Rows returned and affected, warmer colours for large counts
+    let (rows_returned, rows_affected) = rows;
+    for (count, label) in [(rows_returned, "rows"), (rows_affected, "affected")] {
+        let Some(count) = count else {
+            continue;
+        };
+        let rows_text = format!(" {} {} ", format_count(count), label);
+        used_width += rows_text.chars().count();
+        let fg = if count < 1000 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        header_spans.push(Span::styled(
+            rows_text,
+            if is_flashing {
+                Style::default().bg(flash_bg).fg(flash_fg)
+            } else {
+                Style::default().bg(row_count_color(count)).fg(fg)
+            },
+        ));
+    }
+
+    // Show which connection the query came from
+    if let Some(source) = source {
+        let source_text = format!(" {} ", source);
+        used_width += source_text.chars().count();
+        header_spans.push(Span::styled(
+            source_text,
+            if is_flashing {
+                Style::default().bg(flash_bg).fg(flash_fg)
+            } else {
+                Style::default()
+                    .bg(Color::Rgb(50, 50, 70))
+                    .fg(Color::Rgb(150, 190, 255))
+            },
+        ));
+    }
+
+    // Bookmarked queries are kept when older entries are evicted
+    if is_bookmarked {
+        let bookmark_text = " ★ ";
+        used_width += bookmark_text.chars().count();
+        header_spans.push(Span::styled(
+            bookmark_text,
+            if is_flashing {
+                Style::default().bg(flash_bg).fg(flash_fg)
+            } else {
+                Style::default()
+                    .bg(Color::Rgb(255, 215, 0))
+                    .fg(Color::Black) // Gold, like pinned groups
+            },
+        ));
+    }
+
+    // Failed queries, with their SQLSTATE when known
+    if let Some(sqlstate) = error {
+        let error_text = match sqlstate {
+            Some(sqlstate) => format!(" ERROR {} ", sqlstate),
+            None => " ERROR ".to_string(),
+        };
+        used_width += error_text.chars().count();
+        header_spans.push(Span::styled(
+            error_text,
+            if is_flashing {
+                Style::default().bg(flash_bg).fg(flash_fg)
+            } else {
+                Style::default()
+                    .bg(Color::Rgb(200, 40, 40))
+                    .fg(Color::White)
+            },
+        ));
+    }
+
     // Add query badge if applicable
     if let Some(badge) = detect_query_badge(sql_statement) {
         // Add spacing before badge
@@ -102,7 +171,7 @@ pub fn render_header_row(
         ));
 
         // Make badge more compact and less prominent
-        let badge_text = format!("{}", badge.label);
+        let badge_text = badge.label.to_string();
         header_spans.push(Span::styled(
             badge_text.clone(),
             if is_flashing {
@@ -129,76 +198,209 @@ pub fn render_header_row(
 }
 
 // Render a group header for the grouped accordion
+#[allow(clippy::too_many_arguments)]
 pub fn render_group_header(
     group: &RequestGroup,
     message_count: usize,
     is_expanded: bool,
     is_pinned: bool,
+    n_plus_one: Option<&NPlusOne>,
+    summary: &GroupSummary,
+    request: Option<&RequestSummary>,
     width: usize,
 ) -> ratatui::widgets::ListItem<'static> {
+    let error_count = summary.error_count;
     let arrow = if is_expanded { "▼" } else { "►" };
     let method_color = crate::get_http_method_color(&group.http_method);
-    
+
     let _header_text = format!(
         " {} [{}] {} {}",
-        arrow,
-        message_count,
-        group.http_method,
-        group.endpoint
+        arrow, message_count, group.http_method, group.endpoint
     );
-    
+
+    // Groups with failed queries get a red arrow
+    let arrow_bg = if error_count > 0 {
+        Color::Rgb(200, 40, 40)
+    } else {
+        Color::Rgb(60, 60, 60)
+    };
     let mut spans = vec![
+        // Caller tree nodes are indented by their level
+        Span::styled(
+            "  ".repeat(group.depth()),
+            Style::default().bg(Color::Black),
+        ),
         Span::styled(
             format!(" {} ", arrow),
-            Style::default().bg(Color::Rgb(60, 60, 60)).fg(Color::White)
+            Style::default().bg(arrow_bg).fg(Color::White),
         ),
         Span::styled(
             format!(" [{}] ", message_count),
-            Style::default().bg(Color::Rgb(80, 80, 80)).fg(Color::Yellow)
+            Style::default()
+                .bg(Color::Rgb(80, 80, 80))
+                .fg(Color::Yellow),
         ),
         Span::styled(
             format!(" {} ", group.http_method),
-            Style::default().bg(method_color).fg(Color::Black)
+            Style::default().bg(method_color).fg(Color::Black),
         ),
         Span::styled(
             format!(" {} ", group.endpoint),
-            Style::default().bg(Color::Rgb(40, 40, 40)).fg(Color::White)
+            Style::default().bg(Color::Rgb(40, 40, 40)).fg(Color::White),
         ),
     ];
-    
+
+    // Add source when grouping by source
+    if let Some(source) = &group.source {
+        spans.push(Span::styled(
+            format!(" {} ", source),
+            Style::default()
+                .bg(Color::Rgb(50, 50, 70))
+                .fg(Color::Rgb(150, 190, 255)),
+        ));
+    }
+
+    // A single request instance shows its id and wall-clock span
+    if let Some(request_id) = &group.request_id {
+        let short_id: String = request_id.chars().take(16).collect();
+        spans.push(Span::styled(
+            format!(" #{} ", short_id),
+            Style::default()
+                .bg(Color::Rgb(50, 50, 70))
+                .fg(Color::Rgb(200, 200, 200)),
+        ));
+    }
+    if let Some(request) = request {
+        spans.push(Span::styled(
+            format!(" span {} ", crate::format_duration(request.span_ms).trim()),
+            Style::default().bg(Color::Rgb(80, 80, 80)).fg(Color::White),
+        ));
+    }
+
     // Add pin indicator if the group is pinned
     if is_pinned {
         spans.push(Span::styled(
             " 📌 ",
-            Style::default().bg(Color::Rgb(255, 215, 0)).fg(Color::Black) // Gold background
+            Style::default()
+                .bg(Color::Rgb(255, 215, 0))
+                .fg(Color::Black), // Gold background
         ));
     }
-    
-    let used_width: usize = spans.iter().map(|s| s.content.len()).sum();
-    if used_width < width {
-        let remaining_space = " ".repeat(width - used_width);
+
+    if error_count > 0 {
+        spans.push(Span::styled("  ", Style::default().bg(Color::Black)));
         spans.push(Span::styled(
-            remaining_space,
-            Style::default().bg(Color::Black),
+            format!(" ERROR ×{} ", error_count),
+            Style::default()
+                .bg(Color::Rgb(200, 40, 40))
+                .fg(Color::White),
         ));
     }
-    
-    let lines = vec![
-        Line::from(spans),
-        Line::from(Span::styled(
-            "─".repeat(width),
-            Style::default().fg(Color::Rgb(80, 80, 80)),
-        )),
-    ];
-    
+
+    // Warn about a likely N+1 pattern
+    if let Some(n_plus_one) = n_plus_one {
+        spans.push(Span::styled("  ", Style::default().bg(Color::Black)));
+        spans.push(Span::styled(
+            format!(" N+1 ×{} ", n_plus_one.count),
+            Style::default()
+                .bg(Color::Rgb(255, 140, 0))
+                .fg(Color::Black), // Dark orange
+        ));
+    }
+
+    // Aggregates are right-aligned so they line up between groups, against the same visible
+    // width as the statement lines of expanded queries
+    let metrics = group_metric_spans(summary);
+    let used_width: usize = spans.iter().map(Span::width).sum();
+    let metrics_width: usize = metrics.iter().map(Span::width).sum();
+    spans.push(Span::styled(
+        " ".repeat(
+            width
+                .saturating_sub(4)
+                .saturating_sub(used_width + metrics_width)
+                .max(1),
+        ),
+        Style::default().bg(Color::Black),
+    ));
+    spans.extend(metrics);
+
+    let mut lines = vec![Line::from(spans)];
+
+    // Collapsed groups summarize the repeated statement
+    if let Some(n_plus_one) = n_plus_one
+        && !is_expanded
+    {
+        let summary = format!(
+            "    ⚠ {} ×{}",
+            crate::stats::summarize_fingerprint(&n_plus_one.fingerprint),
+            n_plus_one.count
+        );
+        let summary: String = summary.chars().take(width).collect();
+        lines.push(Line::from(Span::styled(
+            summary,
+            Style::default().fg(Color::Rgb(255, 140, 0)),
+        )));
+    }
+
+    lines.push(Line::from(Span::styled(
+        "─".repeat(width),
+        Style::default().fg(Color::Rgb(80, 80, 80)),
+    )));
+
     ratatui::widgets::ListItem::new(lines)
 }
 
-// Render grouped accordions
-pub fn render_grouped_accordions(
+// Number of recent queries drawn in a group header's sparkline
+const SPARKLINE_LEN: usize = 12;
+
+// Total, mean and max duration, each coloured like a query of that duration, followed by a
+// sparkline of the most recent durations
+fn group_metric_spans(summary: &GroupSummary) -> Vec<Span<'static>> {
+    let metric = |label: &str, ms: u64| {
+        let (r, g, b) = crate::interpolate_color(ms);
+        Span::styled(
+            format!(" {} {:>7} ", label, crate::format_duration(ms)),
+            Style::default().bg(Color::Rgb(r, g, b)).fg(Color::Black),
+        )
+    };
+    let mut spans = vec![
+        metric("Σ", summary.total_ms),
+        Span::raw(" "),
+        metric("μ", summary.mean_ms),
+        Span::raw(" "),
+        metric("max", summary.max_ms),
+        Span::raw(" "),
+    ];
+
+    // Bars are scaled to the slowest of the recent queries, newest on the right
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let sparkline_bg = Color::Rgb(30, 30, 30);
+    let peak = summary.recent_ms.iter().copied().max().unwrap_or(0).max(1);
+    spans.push(Span::styled(
+        " ".repeat(SPARKLINE_LEN.saturating_sub(summary.recent_ms.len()) + 1),
+        Style::default().bg(sparkline_bg),
+    ));
+    for &ms in &summary.recent_ms {
+        let level = (ms * (BARS.len() as u64 - 1)).div_ceil(peak) as usize;
+        let (r, g, b) = crate::interpolate_color(ms);
+        spans.push(Span::styled(
+            BARS[level].to_string(),
+            Style::default().bg(sparkline_bg).fg(Color::Rgb(r, g, b)),
+        ));
+    }
+    spans.push(Span::styled(" ", Style::default().bg(sparkline_bg)));
+    spans
+}
+
+// Render one row of the accordion list: a group header or one of its messages
+#[allow(clippy::too_many_arguments)]
+pub fn render_accordion_row(
+    flat_index: usize,
+    item: &FlatNavigationItem,
     grouped_messages: &GroupedLogMessages,
     expanded_groups: &std::collections::HashSet<RequestGroup>,
-    expanded_uids: &std::collections::HashSet<String>,
+    expanded_ids: &std::collections::HashSet<u64>,
+    bookmarked_ids: &std::collections::HashSet<u64>,
     copy_flash_state: Option<(usize, std::time::Instant)>,
     list_state: &ratatui::widgets::ListState,
     scroll_mode: bool,
@@ -206,83 +408,107 @@ pub fn render_grouped_accordions(
     scroll_cursors: &std::collections::HashMap<usize, usize>,
     max_expanded_height: usize,
     width: usize,
-    filter_text: &str,
     pinned_groups: &std::collections::HashSet<RequestGroup>,
-) -> Vec<ratatui::widgets::ListItem<'static>> {
-    let mut items = Vec::new();
-    let mut flat_index = 0; // Track flattened index for selection
-    
-    for (group, messages) in &grouped_messages.groups {
-        // Filter messages within the group
-        let filtered_messages: Vec<&SqlLogMessage> = if filter_text.is_empty() {
-            messages.iter().collect()
-        } else {
-            messages.iter().filter(|msg| {
-                let method_match = if msg.http_method.is_none() {
-                    "CALL".to_lowercase().contains(&filter_text.to_lowercase())
-                } else {
-                    msg.http_method
-                        .as_ref()
-                        .map_or(false, |method| method.to_lowercase().contains(&filter_text.to_lowercase()))
-                };
-
-                let endpoint_match = msg
-                    .endpoint
-                    .as_ref()
-                    .map_or(false, |endpoint| endpoint.to_lowercase().contains(&filter_text.to_lowercase()));
+    render_cache: &mut RenderCache,
+) -> ratatui::widgets::ListItem<'static> {
+    match item {
+        FlatNavigationItem::GroupHeader(group, messages) => render_group_header(
+            group,
+            messages.len(),
+            expanded_groups.contains(group),
+            pinned_groups.contains(group),
+            grouped_messages.n_plus_one(group),
+            &crate::stats::summarize_group(messages, SPARKLINE_LEN),
+            group
+                .request_id
+                .is_some()
+                .then(|| crate::stats::summarize_request(messages))
+                .as_ref(),
+            width,
+        ),
+        FlatNavigationItem::Message(message) => render_accordion_item(
+            flat_index,
+            message,
+            expanded_ids,
+            bookmarked_ids,
+            copy_flash_state,
+            list_state,
+            scroll_mode,
+            scroll_offsets,
+            scroll_cursors,
+            max_expanded_height,
+            width,
+            render_cache,
+        ),
+    }
+}
 
-                let caller_class_match = msg
-                    .caller_class
-                    .as_ref()
-                    .map_or(false, |class| class.to_lowercase().contains(&filter_text.to_lowercase()));
+/// Render only the list items that fit in a viewport of `height` lines. The window starts at
+/// `offset` when the selected item is visible from there, otherwise it scrolls just far enough to
+/// show it. Returns the index of the first rendered item and the rendered items.
+pub fn render_viewport(
+    item_count: usize,
+    selected: usize,
+    offset: usize,
+    height: usize,
+    mut render_item: impl FnMut(usize) -> ratatui::widgets::ListItem<'static>,
+) -> (usize, Vec<ratatui::widgets::ListItem<'static>>) {
+    if item_count == 0 {
+        return (0, Vec::new());
+    }
+    let selected = selected.min(item_count - 1);
+    let mut first = offset.min(selected);
+    let mut window = std::collections::VecDeque::new();
+    let mut used = 0;
 
-                let caller_method_match = msg
-                    .caller_method
-                    .as_ref()
-                    .map_or(false, |method| method.to_lowercase().contains(&filter_text.to_lowercase()));
+    // Keep the current offset as long as the selection fits below it
+    let mut next = first;
+    while next <= selected {
+        let item = render_item(next);
+        used += item.height();
+        window.push_back(item);
+        next += 1;
+        if used > height && next <= selected {
+            break;
+        }
+    }
 
-                method_match || endpoint_match || caller_class_match || caller_method_match
-            }).collect()
-        };
-        
-        // Skip groups with no matching messages
-        if filtered_messages.is_empty() {
-            continue;
+    if next <= selected {
+        // The selection is below the viewport, so build the window upwards from it
+        window.clear();
+        let item = render_item(selected);
+        used = item.height();
+        window.push_back(item);
+        first = selected;
+        while first > 0 {
+            let item = render_item(first - 1);
+            if used + item.height() > height {
+                break;
+            }
+            used += item.height();
+            window.push_front(item);
+            first -= 1;
         }
-        
-        // Render group header
-        let is_group_expanded = expanded_groups.contains(group);
-        let is_pinned = pinned_groups.contains(group);
-        let group_item = render_group_header(group, filtered_messages.len(), is_group_expanded, is_pinned, width);
-        items.push(group_item);
-        flat_index += 1;
-        
-        // If group is expanded, render individual messages
-        if is_group_expanded {
-            // Sort messages by timestamp (most recent first)
-            let mut sorted_messages = filtered_messages;
-            sorted_messages.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-            
-            for (_msg_index, message) in sorted_messages.iter().enumerate() {
-                let item = render_accordion_item(
-                    flat_index,
-                    message,
-                    expanded_uids,
-                    copy_flash_state,
-                    list_state,
-                    scroll_mode,
-                    scroll_offsets,
-                    scroll_cursors,
-                    max_expanded_height,
-                    width,
-                );
-                items.push(item);
-                flat_index += 1;
+        next = selected + 1;
+    } else {
+        // The selected item itself overflows the viewport, so drop items from the top
+        while used > height && first < selected {
+            if let Some(item) = window.pop_front() {
+                used -= item.height();
             }
+            first += 1;
         }
     }
-    
-    items
+
+    // Fill the rest of the viewport below the selection
+    while next < item_count && used < height {
+        let item = render_item(next);
+        used += item.height();
+        window.push_back(item);
+        next += 1;
+    }
+
+    (first, window.into())
 }
 
 /// Render a single accordion item for the SQL log list.
@@ -290,7 +516,8 @@ pub fn render_grouped_accordions(
 pub fn render_accordion_item(
     index: usize,
     line: &crate::SqlLogMessage,
-    expanded_uids: &std::collections::HashSet<String>,
+    expanded_ids: &std::collections::HashSet<u64>,
+    bookmarked_ids: &std::collections::HashSet<u64>,
     copy_flash_state: Option<(usize, std::time::Instant)>,
     list_state: &ratatui::widgets::ListState,
     scroll_mode: bool,
@@ -298,6 +525,7 @@ pub fn render_accordion_item(
     scroll_cursors: &std::collections::HashMap<usize, usize>,
     max_expanded_height: usize,
     width: usize,
+    render_cache: &mut RenderCache,
 ) -> ratatui::widgets::ListItem<'static> {
     use ratatui::{
         style::Style,
@@ -324,11 +552,9 @@ pub fn render_accordion_item(
     let flash_bg = ratatui::style::Color::Rgb(0, 255, 0);
     let flash_fg = ratatui::style::Color::Rgb(0, 0, 0);
     let formatted_duration = crate::format_duration(line.duration);
-    let is_expanded = match &line.uid {
-        Some(uid) => expanded_uids.contains(uid),
-        None => false,
-    };
+    let is_expanded = expanded_ids.contains(&line.id);
     let arrow = if is_expanded { "▼" } else { "►" };
+    let is_bookmarked = bookmarked_ids.contains(&line.id);
     let mut lines = vec![];
     if is_expanded {
         let (_endpoint_str, _http_method) = if line.http_method.is_none() {
@@ -346,13 +572,17 @@ pub fn render_accordion_item(
                 line.http_method.clone().unwrap_or("UNKNOWN".to_string()),
             )
         };
-        let time_str = extract_time_from_timestamp(&line.timestamp);
+        let time_str = message_time(line);
         let header_line = render_header_row(
             arrow,
             &formatted_duration,
             sql_len,
             sql_color,
             &time_str,
+            (line.rows_returned, line.rows_affected),
+            line.source.as_deref(),
+            is_bookmarked,
+            line.is_error().then_some(line.sqlstate.as_deref()),
             &line.statement,
             is_flashing,
             flash_bg,
@@ -362,71 +592,12 @@ pub fn render_accordion_item(
         );
         lines.push(header_line);
         let max_line_width = width.saturating_sub(4);
-        let sql_bg_color = ratatui::style::Color::Black;
-        let mut all_content_lines = Vec::new();
-        if line.statement.contains("[-- Batch Command") {
-            let mut current_batch_sql = String::new();
-            let mut batch_number = 1;
-            for statement_line in line.statement.lines() {
-                if statement_line.starts_with("[-- Batch Command") {
-                    if !current_batch_sql.trim().is_empty() {
-                        let batch_header = format!("[-- Batch Command {}]", batch_number);
-                        all_content_lines.push(Line::from(Span::styled(
-                            format!("  {:<width$}  ", batch_header, width = max_line_width),
-                            Style::default()
-                                .bg(ratatui::style::Color::Rgb(30, 30, 30))
-                                .fg(ratatui::style::Color::Yellow),
-                        )));
-                        all_content_lines.extend(render_sql_lines(
-                            &current_batch_sql,
-                            max_line_width,
-                            sql_bg_color,
-                        ));
-                        all_content_lines.push(Line::from(Span::styled(
-                            format!("  {:<width$}  ", "", width = max_line_width),
-                            Style::default().bg(sql_bg_color),
-                        )));
-                        batch_number += 1;
-                    }
-                    current_batch_sql.clear();
-                } else {
-                    if !current_batch_sql.is_empty() {
-                        current_batch_sql.push('\n');
-                    }
-                    current_batch_sql.push_str(statement_line);
-                }
-            }
-            if !current_batch_sql.trim().is_empty() {
-                let batch_header = format!("[-- Batch Command {}]", batch_number);
-                all_content_lines.push(Line::from(Span::styled(
-                    format!("  {:<width$}  ", batch_header, width = max_line_width),
-                    Style::default()
-                        .bg(ratatui::style::Color::Rgb(40, 40, 40))
-                        .fg(ratatui::style::Color::Yellow),
-                )));
-                all_content_lines.extend(render_sql_lines(
-                    &current_batch_sql,
-                    max_line_width,
-                    sql_bg_color,
-                ));
-            }
-        } else {
-            all_content_lines.extend(render_sql_lines(
-                &line.statement,
-                max_line_width,
-                sql_bg_color,
-            ));
-            all_content_lines.push(Line::from(Span::styled(
-                format!(
-                    "  {:<width$}  ",
-                    "=== END STATEMENT ===",
-                    width = max_line_width
-                ),
-                Style::default()
-                    .bg(ratatui::style::Color::Rgb(50, 50, 50))
-                    .fg(ratatui::style::Color::White),
-            )));
+        // Errors stay visible above the statement while it scrolls
+        if line.is_error() {
+            lines.extend(render_error_lines(line, max_line_width));
         }
+        let sql_bg_color = ratatui::style::Color::Black;
+        let all_content_lines = render_cache.statement_lines(line, max_line_width);
         // Clamp scroll_offset to valid range to prevent blank screens
         let total_content_lines = all_content_lines.len();
         let max_scroll_offset = total_content_lines.saturating_sub(max_expanded_height);
@@ -445,7 +616,9 @@ pub fn render_accordion_item(
         if total_content_lines == 0 {
             lines.push(Line::from(Span::styled(
                 "  (no content to display)  ",
-                Style::default().bg(sql_bg_color).fg(ratatui::style::Color::Red),
+                Style::default()
+                    .bg(sql_bg_color)
+                    .fg(ratatui::style::Color::Red),
             )));
         } else {
             // Always show last max_expanded_height lines if scroll_offset is out of bounds
@@ -465,7 +638,9 @@ pub fn render_accordion_item(
             if visible_lines.is_empty() {
                 lines.push(Line::from(Span::styled(
                     "  (content not visible, check scroll position)  ",
-                    Style::default().bg(sql_bg_color).fg(ratatui::style::Color::Yellow),
+                    Style::default()
+                        .bg(sql_bg_color)
+                        .fg(ratatui::style::Color::Yellow),
                 )));
             } else {
                 // Add scroll info if needed
@@ -496,28 +671,24 @@ pub fn render_accordion_item(
                         && list_state.selected() == Some(index + 1)
                         && absolute_line_index == cursor_pos
                     {
-                        let cursor_line = match content_line {
-                            Line { spans, .. } => {
-                                let mut new_spans = Vec::new();
-                                for span in spans {
-                                    new_spans.push(Span::styled(
-                                        span.content.clone(),
-                                        span.style
-                                            .bg(ratatui::style::Color::Blue)
-                                            .fg(ratatui::style::Color::Yellow),
-                                    ));
-                                }
-                                Line::from(new_spans)
-                            }
-                        };
-                        lines.push(cursor_line);
+                        let mut new_spans = Vec::new();
+                        for span in &content_line.spans {
+                            new_spans.push(Span::styled(
+                                span.content.clone(),
+                                span.style
+                                    .bg(ratatui::style::Color::Blue)
+                                    .fg(ratatui::style::Color::Yellow),
+                            ));
+                        }
+                        lines.push(Line::from(new_spans));
                     } else {
                         lines.push((*content_line).clone());
                     }
                 }
                 // Add bottom info if needed
                 if total_content_lines > max_expanded_height {
-                    let end_offset = std::cmp::min(scroll_offset + max_expanded_height, total_content_lines);
+                    let end_offset =
+                        std::cmp::min(scroll_offset + max_expanded_height, total_content_lines);
                     let remaining = total_content_lines.saturating_sub(end_offset);
                     if remaining > 0 {
                         lines.push(Line::from(Span::styled(
@@ -534,6 +705,43 @@ pub fn render_accordion_item(
                 }
             }
         }
+        // Request and trace ids, to look the request up elsewhere
+        let ids: Vec<String> = [
+            ("request", &line.request_id),
+            ("trace", &line.trace_id),
+            ("span", &line.span_id),
+        ]
+        .into_iter()
+        .filter_map(|(label, id)| id.as_ref().map(|id| format!("{} {}", label, id)))
+        .collect();
+        if !ids.is_empty() {
+            let ids_text: String = ids.join("  ").chars().take(max_line_width).collect();
+            lines.push(Line::from(Span::styled(
+                format!("  {:<width$}  ", ids_text, width = max_line_width),
+                Style::default()
+                    .bg(ratatui::style::Color::Rgb(30, 30, 30))
+                    .fg(ratatui::style::Color::Rgb(155, 155, 155)),
+            )));
+        }
+        // Show fields the monitor does not know about below the statement
+        if !line.extra.is_empty() {
+            let extra_text = line
+                .extra
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let extra_text: String = format!("extra: {}", extra_text)
+                .chars()
+                .take(max_line_width)
+                .collect();
+            lines.push(Line::from(Span::styled(
+                format!("  {:<width$}  ", extra_text, width = max_line_width),
+                Style::default()
+                    .bg(ratatui::style::Color::Rgb(30, 30, 30))
+                    .fg(ratatui::style::Color::Rgb(155, 155, 155)),
+            )));
+        }
         // Removed bottom padding line
     } else {
         let (_endpoint_str, _http_method) = if line.http_method.is_none() {
@@ -551,13 +759,17 @@ pub fn render_accordion_item(
                 line.http_method.clone().unwrap_or("UNKNOWN".to_string()),
             )
         };
-        let time_str = extract_time_from_timestamp(&line.timestamp);
+        let time_str = message_time(line);
         let header_line = render_header_row(
             arrow,
             &formatted_duration,
             sql_len,
             sql_color,
             &time_str,
+            (line.rows_returned, line.rows_affected),
+            line.source.as_deref(),
+            is_bookmarked,
+            line.is_error().then_some(line.sqlstate.as_deref()),
             &line.statement,
             is_flashing,
             flash_bg,
@@ -574,18 +786,202 @@ pub fn render_accordion_item(
     ratatui::widgets::ListItem::new(lines)
 }
 
+// Severity, SQLSTATE, message, detail and hint of a failed query
+fn render_error_lines(msg: &SqlLogMessage, max_line_width: usize) -> Vec<Line<'static>> {
+    let heading = match (&msg.severity, &msg.sqlstate) {
+        (Some(severity), Some(sqlstate)) => format!("{} {}", severity, sqlstate),
+        (Some(severity), None) => severity.clone(),
+        (None, Some(sqlstate)) => format!("ERROR {}", sqlstate),
+        (None, None) => "ERROR".to_string(),
+    };
+    let mut rows = vec![format!(
+        "{}: {}",
+        heading,
+        msg.error_message.as_deref().unwrap_or("(no message)")
+    )];
+    if let Some(detail) = &msg.detail {
+        rows.push(format!("DETAIL: {}", detail));
+    }
+    if let Some(hint) = &msg.hint {
+        rows.push(format!("HINT: {}", hint));
+    }
+
+    rows.iter()
+        .flat_map(|row| row.lines())
+        .map(|row| {
+            let row: String = row.chars().take(max_line_width).collect();
+            Line::from(Span::styled(
+                format!("  {:<width$}  ", row, width = max_line_width),
+                Style::default()
+                    .bg(Color::Rgb(70, 20, 20))
+                    .fg(Color::Rgb(255, 170, 170)),
+            ))
+        })
+        .collect()
+}
+
+/// Formatted, highlighted and padded SQL lines per message id and width, so redraws and scroll
+/// handlers do not format and highlight the same statement again.
+#[derive(Default)]
+pub struct RenderCache {
+    statement_lines: HashMap<(u64, usize), Rc<Vec<Line<'static>>>>,
+    // Substitute parameter values into the SQL instead of showing placeholders
+    inline_parameters: bool,
+}
+
+// Expanded messages are few, so a full reset when this is exceeded is good enough
+const MAX_CACHED_STATEMENTS: usize = 512;
+
+impl RenderCache {
+    /// Content lines of an expanded message, rendered on first use.
+    pub fn statement_lines(
+        &mut self,
+        message: &SqlLogMessage,
+        max_line_width: usize,
+    ) -> Rc<Vec<Line<'static>>> {
+        let key = (message.id, max_line_width);
+        if let Some(lines) = self.statement_lines.get(&key) {
+            return lines.clone();
+        }
+        if self.statement_lines.len() >= MAX_CACHED_STATEMENTS {
+            self.statement_lines.clear();
+        }
+        let statement = params::statement_text(message, self.inline_parameters);
+        let mut lines = render_statement_lines(&statement, max_line_width);
+        if let Some(parameters) = &message.parameters
+            && !parameters.is_empty()
+        {
+            lines.extend(render_parameter_lines(parameters, max_line_width));
+        }
+        let lines = Rc::new(lines);
+        self.statement_lines.insert(key, lines.clone());
+        lines
+    }
+
+    pub fn inline_parameters(&self) -> bool {
+        self.inline_parameters
+    }
+
+    /// Switch between placeholders and inlined values. Every statement renders differently, so
+    /// the cache starts over.
+    pub fn toggle_inline_parameters(&mut self) {
+        self.inline_parameters = !self.inline_parameters;
+        self.statement_lines.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.statement_lines.clear();
+    }
+}
+
+// Table of placeholder names and values below the statement
+fn render_parameter_lines(parameters: &Parameters, max_line_width: usize) -> Vec<Line<'static>> {
+    let entries = parameters.entries();
+    let name_width = entries
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let row_style = Style::default().bg(ratatui::style::Color::Rgb(30, 30, 30));
+
+    let mut lines = vec![Line::from(Span::styled(
+        format!("  {:<width$}  ", "Parameters", width = max_line_width),
+        row_style.fg(ratatui::style::Color::Yellow),
+    ))];
+    for (name, value) in entries {
+        let name_text = format!("{:<width$}  ", name, width = name_width);
+        let value_width = max_line_width.saturating_sub(name_text.chars().count());
+        let value_text: String = params::postgres_literal(value)
+            .chars()
+            .take(value_width)
+            .collect();
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("  {}", name_text),
+                row_style.fg(ratatui::style::Color::Rgb(150, 190, 255)),
+            ),
+            Span::styled(
+                format!("{:<width$}  ", value_text, width = value_width),
+                row_style.fg(ratatui::style::Color::White),
+            ),
+        ]));
+    }
+    lines
+}
+
+// Split batches into their commands and render every part as formatted, highlighted SQL
+fn render_statement_lines(statement: &str, max_line_width: usize) -> Vec<Line<'static>> {
+    let sql_bg_color = ratatui::style::Color::Black;
+    let mut all_content_lines = Vec::new();
+    if statement.contains("[-- Batch Command") {
+        let mut current_batch_sql = String::new();
+        let mut batch_number = 1;
+        for statement_line in statement.lines() {
+            if statement_line.starts_with("[-- Batch Command") {
+                if !current_batch_sql.trim().is_empty() {
+                    let batch_header = format!("[-- Batch Command {}]", batch_number);
+                    all_content_lines.push(Line::from(Span::styled(
+                        format!("  {:<width$}  ", batch_header, width = max_line_width),
+                        Style::default()
+                            .bg(ratatui::style::Color::Rgb(30, 30, 30))
+                            .fg(ratatui::style::Color::Yellow),
+                    )));
+                    all_content_lines.extend(render_sql_lines(
+                        &current_batch_sql,
+                        max_line_width,
+                        sql_bg_color,
+                    ));
+                    all_content_lines.push(Line::from(Span::styled(
+                        format!("  {:<width$}  ", "", width = max_line_width),
+                        Style::default().bg(sql_bg_color),
+                    )));
+                    batch_number += 1;
+                }
+                current_batch_sql.clear();
+            } else {
+                if !current_batch_sql.is_empty() {
+                    current_batch_sql.push('\n');
+                }
+                current_batch_sql.push_str(statement_line);
+            }
+        }
+        if !current_batch_sql.trim().is_empty() {
+            let batch_header = format!("[-- Batch Command {}]", batch_number);
+            all_content_lines.push(Line::from(Span::styled(
+                format!("  {:<width$}  ", batch_header, width = max_line_width),
+                Style::default()
+                    .bg(ratatui::style::Color::Rgb(40, 40, 40))
+                    .fg(ratatui::style::Color::Yellow),
+            )));
+            all_content_lines.extend(render_sql_lines(
+                &current_batch_sql,
+                max_line_width,
+                sql_bg_color,
+            ));
+        }
+    } else {
+        all_content_lines.extend(render_sql_lines(statement, max_line_width, sql_bg_color));
+        all_content_lines.push(Line::from(Span::styled(
+            format!(
+                "  {:<width$}  ",
+                "=== END STATEMENT ===",
+                width = max_line_width
+            ),
+            Style::default()
+                .bg(ratatui::style::Color::Rgb(50, 50, 50))
+                .fg(ratatui::style::Color::White),
+        )));
+    }
+    all_content_lines
+}
+
 /// Render SQL lines with syntax highlighting and padding.
 pub fn render_sql_lines(
     sql: &str,
     max_line_width: usize,
     sql_bg_color: Color,
 ) -> Vec<Line<'static>> {
-    let format_options = sqlformat::FormatOptions {
-        indent: sqlformat::Indent::Spaces(2),
-        uppercase: Some(false),
-        lines_between_queries: 1,
-        ignore_case_convert: Some(vec![]),
-    };
+    let format_options = crate::format::format_options();
     let formatted_sql = sqlformat::format(sql, &sqlformat::QueryParams::None, &format_options);
     let formatted_lines: Vec<&str> = formatted_sql.lines().collect();
     let mut lines = Vec::new();
@@ -648,6 +1044,591 @@ pub fn render_sql_lines(
     lines
 }
 
+// Render the fingerprint statistics screen, one row per normalized statement
+pub fn render_stats_screen(
+    f: &mut ratatui::Frame,
+    area: ratatui::layout::Rect,
+    fingerprint_stats: &[FingerprintStats],
+    selected: usize,
+) {
+    use ratatui::{
+        layout::Constraint,
+        widgets::{Block, Borders, Cell, Row, Table, TableState},
+    };
+
+    let duration_cell = |ms: u64| {
+        let (r, g, b) = crate::interpolate_color(ms);
+        Cell::from(crate::format_duration(ms)).style(Style::default().fg(Color::Rgb(r, g, b)))
+    };
+
+    let rows: Vec<Row> = fingerprint_stats
+        .iter()
+        .map(|stat| {
+            Row::new(vec![
+                Cell::from(format!("{:>6}", stat.calls)),
+                duration_cell(stat.total_ms),
+                duration_cell(stat.mean_ms.round() as u64),
+                duration_cell(stat.p50_ms),
+                duration_cell(stat.p95_ms),
+                duration_cell(stat.p99_ms),
+                duration_cell(stat.max_ms),
+                Cell::from(
+                    stat.mean_rows
+                        .map(|rows| format!("{:>7}", format_count(rows.round() as u64)))
+                        .unwrap_or_else(|| format!("{:>7}", "-")),
+                ),
+                Cell::from(
+                    stat.max_rows
+                        .map(|rows| format!("{:>8}", format_count(rows)))
+                        .unwrap_or_else(|| format!("{:>8}", "-")),
+                ),
+                Cell::from(format_time(stat.last_seen)),
+                Cell::from(stat.fingerprint.clone())
+                    .style(Style::default().fg(Color::Rgb(245, 222, 179))),
+            ])
+        })
+        .collect();
+
+    let header = Row::new(vec![
+        "Calls",
+        "Total",
+        "Mean",
+        "p50",
+        "p95",
+        "p99",
+        "Max",
+        "Rows",
+        "Max rows",
+        "Last seen",
+        "Fingerprint",
+    ])
+    .style(Style::default().fg(Color::Yellow))
+    .bottom_margin(1);
+
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(7),
+        Constraint::Length(8),
+        Constraint::Length(9),
+        Constraint::Min(20),
+    ];
+
+    let total_ms: u64 = fingerprint_stats.iter().map(|stat| stat.total_ms).sum();
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Rgb(0, 149, 255)))
+                .title(format!(
+                    " Query statistics - {} fingerprints, {} total - Esc to return ",
+                    fingerprint_stats.len(),
+                    crate::format_duration(total_ms)
+                ))
+                .title_style(Style::default().fg(Color::White)),
+        )
+        .highlight_style(Style::default().bg(Color::Rgb(60, 60, 60)))
+        .highlight_symbol("► ");
+
+    let mut table_state = TableState::default();
+    if !fingerprint_stats.is_empty() {
+        table_state.select(Some(selected));
+    }
+    f.render_stateful_widget(table, area, &mut table_state);
+}
+
+// Render the dashboard: query rate and database time over the configured window, the duration
+// histogram and statement types, and the slowest queries and busiest endpoints
+pub fn render_dashboard_screen(
+    f: &mut ratatui::Frame,
+    area: ratatui::layout::Rect,
+    dashboard: &Dashboard,
+    retained: usize,
+) {
+    use ratatui::{
+        layout::{Constraint, Direction, Layout},
+        symbols::Marker,
+        widgets::{
+            Axis, Bar, BarChart, BarGroup, Block, Borders, Cell, Chart, Dataset, GraphType, Row,
+            Sparkline, Table,
+        },
+    };
+
+    let panel = |title: String| {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Rgb(0, 149, 255)))
+            .title(title)
+            .title_style(Style::default().fg(Color::White))
+    };
+    let outer = panel(format!(
+        " Dashboard - {} queries retained - Esc to return ",
+        retained
+    ));
+    let inner = outer.inner(area);
+    f.render_widget(outer, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(10), // query rate
+            Constraint::Length(4),  // database time
+            Constraint::Length(9),  // histogram and statement types
+            Constraint::Min(0),     // top 10 tables
+        ])
+        .split(inner);
+
+    // Queries per second, x in seconds relative to the end of the window
+    let window_secs = dashboard.queries_per_second.len();
+    let points: Vec<(f64, f64)> = dashboard
+        .queries_per_second
+        .iter()
+        .enumerate()
+        .map(|(slot, &count)| ((slot + 1) as f64 - window_secs as f64, count as f64))
+        .collect();
+    let peak_rate = dashboard
+        .queries_per_second
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let total_in_window: u64 = dashboard.queries_per_second.iter().sum();
+    let dataset = Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Rgb(97, 175, 254)))
+        .data(&points);
+    let axis_style = Style::default().fg(Color::Rgb(150, 150, 150));
+    let chart = Chart::new(vec![dataset])
+        .block(panel(format!(
+            " Queries per second - last {} until {} - {} queries, {:.1}/s ",
+            format_window(window_secs),
+            format_time(dashboard.window_end),
+            total_in_window,
+            total_in_window as f64 / window_secs.max(1) as f64
+        )))
+        .x_axis(
+            Axis::default()
+                .style(axis_style)
+                .bounds([-(window_secs as f64), 0.0])
+                .labels(vec![
+                    Span::raw(format!("-{}", format_window(window_secs))),
+                    Span::raw(format!("-{}", format_window(window_secs / 2))),
+                    Span::raw("0"),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(axis_style)
+                .bounds([0.0, peak_rate as f64])
+                .labels(vec![Span::raw("0"), Span::raw(peak_rate.to_string())]),
+        );
+    f.render_widget(chart, rows[0]);
+
+    // Database time per second, the newest seconds that fit
+    let sparkline_block = panel(" Database time per second ".to_string());
+    let visible = sparkline_block.inner(rows[1]).width as usize;
+    let db_ms = &dashboard.db_ms_per_second;
+    let sparkline = Sparkline::default()
+        .block(sparkline_block)
+        .data(&db_ms[db_ms.len().saturating_sub(visible)..])
+        .style(Style::default().fg(Color::Rgb(255, 252, 66)));
+    f.render_widget(sparkline, rows[1]);
+
+    let middle = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(rows[2]);
+
+    let histogram: Vec<Bar> = dashboard
+        .histogram
+        .iter()
+        .map(|bucket| {
+            let (r, g, b) = crate::interpolate_color(bucket.min_ms);
+            Bar::default()
+                .value(bucket.count)
+                .text_value(format_count(bucket.count))
+                .label(Line::from(bucket.label))
+                .style(Style::default().fg(Color::Rgb(r, g, b)))
+                .value_style(Style::default().bg(Color::Rgb(r, g, b)).fg(Color::Black))
+        })
+        .collect();
+    let histogram_chart = BarChart::default()
+        .block(panel(" Duration histogram ".to_string()))
+        .data(BarGroup::default().bars(&histogram))
+        .bar_width(6)
+        .bar_gap(1);
+    f.render_widget(histogram_chart, middle[0]);
+
+    let statement_types: Vec<Bar> = dashboard
+        .statement_types
+        .iter()
+        .map(|kind| {
+            Bar::default()
+                .value(kind.count)
+                .text_value(format_count(kind.count))
+                .label(Line::from(kind.label.clone()))
+                .style(Style::default().fg(kind.color))
+                .value_style(Style::default().bg(kind.color).fg(Color::Black))
+        })
+        .collect();
+    let statement_chart = BarChart::default()
+        .block(panel(" Statement types ".to_string()))
+        .data(BarGroup::default().bars(&statement_types))
+        .bar_width(6)
+        .bar_gap(1);
+    f.render_widget(statement_chart, middle[1]);
+
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(rows[3]);
+
+    let duration_cell = |ms: u64| {
+        let (r, g, b) = crate::interpolate_color(ms);
+        Cell::from(crate::format_duration(ms)).style(Style::default().fg(Color::Rgb(r, g, b)))
+    };
+    let header_style = Style::default().fg(Color::Yellow);
+
+    let slowest: Vec<Row> = dashboard
+        .slowest
+        .iter()
+        .map(|msg| {
+            let group = RequestGroup::from_message(msg, crate::config::GroupingMode::Endpoint);
+            Row::new(vec![
+                duration_cell(msg.duration),
+                Cell::from(message_time(msg)),
+                Cell::from(format!("{} {}", group.http_method, group.endpoint)),
+                Cell::from(
+                    msg.statement
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+                .style(Style::default().fg(Color::Rgb(245, 222, 179))),
+            ])
+        })
+        .collect();
+    let slowest_table = Table::new(
+        slowest,
+        [
+            Constraint::Length(8),
+            Constraint::Length(9),
+            Constraint::Percentage(30),
+            Constraint::Min(20),
+        ],
+    )
+    .header(Row::new(vec!["Duration", "Time", "Endpoint", "SQL"]).style(header_style))
+    .block(panel(" Slowest queries ".to_string()));
+    f.render_widget(slowest_table, bottom[0]);
+
+    let noisiest: Vec<Row> = dashboard
+        .noisiest
+        .iter()
+        .map(|activity| {
+            Row::new(vec![
+                Cell::from(format!("{:>6}", activity.count)),
+                duration_cell(activity.total_ms),
+                duration_cell(activity.total_ms / activity.count.max(1) as u64),
+                Cell::from(activity.label.clone()),
+            ])
+        })
+        .collect();
+    let noisiest_table = Table::new(
+        noisiest,
+        [
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Min(10),
+        ],
+    )
+    .header(Row::new(vec![" Calls", "Total", "Mean", "Endpoint"]).style(header_style))
+    .block(panel(" Busiest endpoints ".to_string()));
+    f.render_widget(noisiest_table, bottom[1]);
+}
+
+// A window length like "5m", "2.5m" or "45s"
+fn format_window(secs: usize) -> String {
+    if secs.is_multiple_of(60) && secs > 0 {
+        format!("{}m", secs / 60)
+    } else if secs >= 60 {
+        format!("{:.1}m", secs as f64 / 60.0)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+// Render the panel listing log lines that could not be parsed, newest first
+pub fn render_rejected_panel(
+    f: &mut ratatui::Frame,
+    area: ratatui::layout::Rect,
+    rejected_lines: &RejectedLines,
+    scroll: u16,
+) {
+    use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(format!(
+            "{} lines rejected this session, showing the last {}",
+            rejected_lines.total,
+            rejected_lines.lines.len()
+        )),
+        Line::from(""),
+    ];
+    if rejected_lines.lines.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No rejected lines",
+            Style::default().fg(Color::Green),
+        )));
+    }
+    for rejected in &rejected_lines.lines {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!(" {} ", rejected.received_at.format("%H:%M:%S")),
+                Style::default()
+                    .bg(Color::Rgb(100, 100, 100))
+                    .fg(Color::White),
+            ),
+            Span::styled(
+                format!(" {} ", rejected.source),
+                Style::default()
+                    .bg(Color::Rgb(50, 50, 70))
+                    .fg(Color::Rgb(150, 190, 255)),
+            ),
+            Span::styled(
+                format!(" {}", rejected.error),
+                Style::default().fg(Color::Rgb(237, 83, 83)),
+            ),
+        ]));
+        lines.push(Line::from(Span::styled(
+            format!("  {}", rejected.raw),
+            Style::default().fg(Color::Rgb(245, 222, 179)),
+        )));
+        lines.push(Line::from(""));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red))
+                .title(" Rejected lines - j/k to scroll, c to clear, Esc to return ")
+                .title_style(Style::default().fg(Color::White)),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0))
+        .style(Style::default().fg(Color::White));
+
+    f.render_widget(paragraph, area);
+}
+
 // This function is deprecated and replaced by inline scroll handling in main.rs
 // The grouped accordion structure makes this centralized function obsolete
 // All scroll handling is now done directly in the scroll mode handlers in main.rs
+
+/// Waterfall of one group's queries: one row per query in start order, with a bar positioned by
+/// start time and as long as the query took.
+pub fn render_timeline_screen(
+    f: &mut ratatui::Frame,
+    area: ratatui::layout::Rect,
+    group: &RequestGroup,
+    messages: &[&SqlLogMessage],
+    selected: usize,
+) {
+    use ratatui::{
+        layout::{Constraint, Direction, Layout},
+        symbols::Marker,
+        widgets::{
+            Block, Borders, Paragraph,
+            canvas::{Canvas, Line as CanvasLine},
+        },
+    };
+
+    let mut title = format!(" Timeline - {} {}", group.http_method, group.endpoint);
+    if let Some(request_id) = &group.request_id {
+        title.push_str(&format!(" #{}", request_id));
+    }
+    let summary = crate::stats::summarize_request(messages);
+    title.push_str(&format!(
+        " - {} queries, db {}, span {} - j/k to select, Esc to return ",
+        messages.len(),
+        crate::format_duration(summary.db_ms).trim(),
+        crate::format_duration(summary.span_ms).trim()
+    ));
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Rgb(0, 149, 255)))
+        .title(title)
+        .title_style(Style::default().fg(Color::White));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if messages.is_empty() {
+        f.render_widget(Paragraph::new("No queries left in this group"), inner);
+        return;
+    }
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(48), Constraint::Min(10)])
+        .split(rows[1]);
+    let (labels_area, bars_area) = (columns[0], columns[1]);
+
+    // Keep the selected query in view
+    let visible_rows = bars_area.height as usize;
+    let selected = selected.min(messages.len() - 1);
+    let first = selected.saturating_sub(visible_rows.saturating_sub(1));
+    let visible = &messages[first..messages.len().min(first + visible_rows)];
+
+    let start = messages
+        .iter()
+        .map(|msg| msg.time)
+        .min()
+        .unwrap_or_default();
+    let offset_ms = |msg: &SqlLogMessage| (msg.time - start).num_milliseconds().max(0) as f64;
+    let span_ms = (summary.span_ms as f64).max(1.0);
+
+    // Time axis above the bars
+    let axis_width = bars_area.width as usize;
+    let end_label = format!("{} ", crate::format_duration(summary.span_ms).trim());
+    let axis = format!(
+        "0{:>width$}",
+        end_label,
+        width = axis_width.saturating_sub(1)
+    );
+    f.render_widget(
+        Paragraph::new(axis).style(Style::default().fg(Color::Rgb(150, 150, 150))),
+        ratatui::layout::Rect {
+            x: bars_area.x,
+            width: bars_area.width,
+            ..rows[0]
+        },
+    );
+
+    let labels: Vec<Line> = visible
+        .iter()
+        .enumerate()
+        .map(|(row, msg)| {
+            let (r, g, b) = crate::interpolate_color(msg.duration);
+            let fingerprint = crate::stats::summarize_fingerprint(&msg.fingerprint);
+            let text = format!(
+                "+{:>7} {:>7} {}",
+                crate::format_duration(offset_ms(msg) as u64).trim(),
+                crate::format_duration(msg.duration).trim(),
+                fingerprint
+            );
+            let text: String = text.chars().take(labels_area.width as usize).collect();
+            let style = if first + row == selected {
+                Style::default().bg(Color::Rgb(60, 60, 60)).fg(Color::White)
+            } else {
+                Style::default().fg(Color::Rgb(r, g, b))
+            };
+            Line::from(Span::styled(
+                format!("{:<width$}", text, width = labels_area.width as usize),
+                style,
+            ))
+        })
+        .collect();
+    f.render_widget(Paragraph::new(labels), labels_area);
+
+    // One cell row per query; very short queries still get a visible sliver
+    let height = visible_rows as f64;
+    let min_width = span_ms / bars_area.width.max(1) as f64;
+    let canvas = Canvas::default()
+        .marker(Marker::Block)
+        .x_bounds([0.0, span_ms])
+        .y_bounds([0.0, height])
+        .paint(|ctx| {
+            for (row, msg) in visible.iter().enumerate() {
+                let y = height - row as f64 - 0.5;
+                let x1 = offset_ms(msg);
+                let x2 = (x1 + msg.duration as f64).max(x1 + min_width).min(span_ms);
+                let color = if first + row == selected {
+                    Color::White
+                } else if msg.is_error() {
+                    Color::Rgb(200, 40, 40)
+                } else {
+                    let (r, g, b) = crate::interpolate_color(msg.duration);
+                    Color::Rgb(r, g, b)
+                };
+                ctx.draw(&CanvasLine {
+                    x1,
+                    y1: y,
+                    x2,
+                    y2: y,
+                    color,
+                });
+            }
+        });
+    f.render_widget(canvas, bars_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Render a list whose items are `heights` lines tall. Returns the first rendered index and the
+    // number of rendered items.
+    fn viewport(
+        heights: &[usize],
+        selected: usize,
+        offset: usize,
+        height: usize,
+    ) -> (usize, usize) {
+        let (first, items) = render_viewport(heights.len(), selected, offset, height, |index| {
+            ratatui::widgets::ListItem::new(vec![Line::from(""); heights[index]])
+        });
+        (first, items.len())
+    }
+
+    #[test]
+    fn empty_list_renders_nothing() {
+        assert_eq!(viewport(&[], 0, 0, 10), (0, 0));
+    }
+
+    #[test]
+    fn offset_is_kept_while_the_selection_is_visible() {
+        assert_eq!(viewport(&[1; 10], 4, 2, 5), (2, 5));
+        assert_eq!(viewport(&[1; 10], 2, 2, 5), (2, 5));
+    }
+
+    #[test]
+    fn selection_below_the_viewport_scrolls_just_far_enough() {
+        assert_eq!(viewport(&[1; 10], 8, 0, 3), (6, 3));
+        // Items of different heights: 8 fits, and 7 as well, but not 6
+        assert_eq!(viewport(&[1, 1, 1, 1, 1, 1, 2, 2, 1, 1], 8, 0, 4), (7, 3));
+    }
+
+    #[test]
+    fn selection_above_the_offset_becomes_the_first_item() {
+        assert_eq!(viewport(&[1; 10], 3, 7, 4), (3, 4));
+    }
+
+    #[test]
+    fn the_last_item_may_be_cut_off() {
+        assert_eq!(viewport(&[2, 2, 2, 2], 2, 0, 5), (1, 3));
+    }
+
+    #[test]
+    fn selected_item_taller_than_the_viewport_is_shown_alone() {
+        assert_eq!(viewport(&[1, 1, 10, 1], 2, 0, 4), (2, 1));
+    }
+
+    #[test]
+    fn selection_is_clamped_to_the_list() {
+        assert_eq!(viewport(&[1; 5], 20, 0, 10), (0, 5));
+    }
+}